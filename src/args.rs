@@ -36,6 +36,21 @@ pub struct Args {
     /// Build all examples
     #[cfg_attr(feature = "clap", clap(long, conflicts_with = "example"))]
     pub examples: bool,
+    /// Build only the specified test target
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub test: Vec<String>,
+    /// Build all test targets
+    #[cfg_attr(feature = "clap", clap(long, conflicts_with = "test"))]
+    pub tests: bool,
+    /// Build only the specified bench target
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub bench: Vec<String>,
+    /// Build all bench targets
+    #[cfg_attr(feature = "clap", clap(long, conflicts_with = "bench"))]
+    pub benches: bool,
+    /// Build all targets
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub all_targets: bool,
 
     /// Build artifacts in release mode, with optimizations
     #[cfg_attr(feature = "clap", clap(long, short))]
@@ -61,6 +76,9 @@ pub struct Args {
     /// Path to Cargo.toml
     #[cfg_attr(feature = "clap", clap(long))]
     pub manifest_path: Option<PathBuf>,
+    /// Override a configuration value with `KEY=VALUE` or a path to an extra config file
+    #[cfg_attr(feature = "clap", clap(long, value_name = "KEY=VALUE|PATH"))]
+    pub config: Vec<String>,
 }
 
 impl Args {
@@ -94,6 +112,21 @@ impl Args {
         if self.examples {
             cmd.arg("--examples");
         }
+        for test in &self.test {
+            cmd.arg("--test").arg(test);
+        }
+        if self.tests {
+            cmd.arg("--tests");
+        }
+        for bench in &self.bench {
+            cmd.arg("--bench").arg(bench);
+        }
+        if self.benches {
+            cmd.arg("--benches");
+        }
+        if self.all_targets {
+            cmd.arg("--all-targets");
+        }
 
         if self.release {
             cmd.arg("--release");
@@ -119,6 +152,9 @@ impl Args {
         if let Some(manifest_path) = self.manifest_path.as_ref() {
             cmd.arg("--manifest-path").arg(manifest_path);
         }
+        for config in &self.config {
+            cmd.arg("--config").arg(config);
+        }
     }
 
     pub fn profile(&self) -> Profile {
@@ -137,6 +173,15 @@ impl Args {
     ///
     /// [`cargo build`]: https://doc.rust-lang.org/cargo/commands/cargo-build.html#target-selection
     pub fn specific_target_selected(&self) -> bool {
-        self.lib || self.bins || self.examples || !self.bin.is_empty() || !self.example.is_empty()
+        self.lib
+            || self.bins
+            || self.examples
+            || self.tests
+            || self.benches
+            || self.all_targets
+            || !self.bin.is_empty()
+            || !self.example.is_empty()
+            || !self.test.is_empty()
+            || !self.bench.is_empty()
     }
 }
@@ -1,14 +1,16 @@
 use std::path::{Path, PathBuf};
 
+use crate::error::{Error, Result};
 use crate::manifest::CrateType;
+use crate::triple::Triple;
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum ArtifactType {
     Lib,
     Bin,
     Example,
-    // Bench,
-    // Test,
+    Test,
+    Bench,
 }
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -16,39 +18,323 @@ pub struct Artifact {
     pub name: String,
     pub path: PathBuf,
     pub r#type: ArtifactType,
+    /// The crate types declared in [`crate-type`], or else [`CrateType::Lib`] for libraries
+    /// and [`CrateType::Bin`] for binaries and examples.
+    ///
+    /// [`crate-type`]: https://doc.rust-lang.org/cargo/reference/cargo-targets.html#the-crate-type-field
+    pub crate_types: Vec<CrateType>,
+    /// Whether the target is compiled with the libtest [`harness`] when it is tested or
+    /// benchmarked, which is the default.
+    ///
+    /// [`harness`]: https://doc.rust-lang.org/cargo/reference/cargo-targets.html#the-harness-field
+    pub harness: bool,
 }
 
 impl Artifact {
+    /// The directory within the [profile build directory][crate::Subcommand::build_dir()]
+    /// where this artifact is placed.
+    ///
+    /// Tests and benchmarks are not uplifted out of `deps`, where cargo appends a
+    /// `-<hash>` suffix to their file names.  For those this is merely the directory that
+    /// contains them, as their [`file_names()`][Self::file_names()] lack that suffix.
     pub fn build_dir(&self) -> &'static Path {
         Path::new(match self.r#type {
             ArtifactType::Lib | ArtifactType::Bin => "",
             ArtifactType::Example => "examples",
+            ArtifactType::Test | ArtifactType::Bench => "deps",
         })
     }
 
-    // TODO: CrateType should be read from the manifest' crate-type array,
-    // and validated that the requested format is in that array
-    pub fn file_name(&self, ty: CrateType, target: &str) -> String {
-        match (self.r#type, ty) {
-            (ArtifactType::Bin | ArtifactType::Example, CrateType::Bin) => {
-                if target.contains("windows") {
-                    format!("{}.exe", self.name)
-                } else if target.contains("wasm") {
-                    format!("{}.wasm", self.name)
-                } else {
-                    self.name.to_string()
+    /// Returns the name of the main file of this artifact when built as `ty` for `target`,
+    /// which must be one of the [declared crate types][Self::crate_types].
+    pub fn file_name(&self, ty: CrateType, target: &str) -> Result<String> {
+        let mut files = self.file_names(ty, target)?;
+        Ok(files.swap_remove(0).name)
+    }
+
+    /// Returns the names of all files that `rustc` produces for this artifact when built as
+    /// `ty` for `target`: the main file first, followed by companion files like import
+    /// libraries and separate debug information.  Debug information is only present when
+    /// enabled in the [profile], and `.dwp` packages only with `split-debuginfo = "packed"`.
+    ///
+    /// [profile]: https://doc.rust-lang.org/cargo/reference/profiles.html#split-debuginfo
+    pub fn file_names(&self, ty: CrateType, target: &str) -> Result<Vec<ArtifactFile>> {
+        // `lib` currently produces an `rlib`, so either spelling satisfies the other
+        let is_rlib = |ty: &CrateType| matches!(ty, CrateType::Lib | CrateType::Rlib);
+        if !self
+            .crate_types
+            .iter()
+            .any(|declared| *declared == ty || (is_rlib(declared) && is_rlib(&ty)))
+        {
+            return Err(Error::UndeclaredCrateType {
+                name: self.name.clone(),
+                artifact: self.r#type,
+                crate_type: ty,
+                declared: self.crate_types.clone(),
+            });
+        }
+
+        let triple = Triple::new(target);
+        // Libraries and debug information use the crate name, in which `-` is not allowed
+        let crate_name = self.name.replace('-', "_");
+        let file = |name: String, flavor| ArtifactFile { name, flavor };
+
+        Ok(match (self.r#type, ty) {
+            (
+                ArtifactType::Bin
+                | ArtifactType::Example
+                | ArtifactType::Test
+                | ArtifactType::Bench,
+                CrateType::Bin,
+            ) => {
+                let main = format!("{}{}", self.name, triple.exe_suffix());
+                let mut files = vec![];
+                if triple.is_emscripten() {
+                    files.push(file(format!("{crate_name}.wasm"), FileFlavor::Normal));
                 }
+                files.extend(debug_info(&triple, &main, &crate_name));
+                files.insert(0, file(main, FileFlavor::Normal));
+                files
             }
-            (ArtifactType::Lib | ArtifactType::Example, CrateType::Lib) => {
-                format!("lib{}.rlib", self.name.replace('-', "_"))
+            (ArtifactType::Lib | ArtifactType::Example, CrateType::Lib | CrateType::Rlib) => {
+                vec![file(format!("lib{crate_name}.rlib"), FileFlavor::Normal)]
             }
             (ArtifactType::Lib | ArtifactType::Example, CrateType::Staticlib) => {
-                format!("lib{}.a", self.name.replace('-', "_"))
+                let (prefix, suffix) = triple.staticlib_prefix_suffix();
+                vec![file(
+                    format!("{prefix}{crate_name}{suffix}"),
+                    FileFlavor::Normal,
+                )]
             }
-            (ArtifactType::Lib | ArtifactType::Example, CrateType::Cdylib) => {
-                format!("lib{}.so", self.name.replace('-', "_"))
+            (
+                ArtifactType::Lib | ArtifactType::Example,
+                CrateType::Dylib | CrateType::Cdylib | CrateType::ProcMacro,
+            ) => {
+                let (prefix, suffix) = triple.dll_prefix_suffix();
+                let main = format!("{prefix}{crate_name}{suffix}");
+                let mut files = vec![file(main.clone(), FileFlavor::Normal)];
+                if triple.is_msvc() {
+                    files.push(file(format!("{main}.lib"), FileFlavor::Linkable));
+                } else if triple.is_windows() {
+                    files.push(file(format!("lib{main}.a"), FileFlavor::Linkable));
+                }
+                files.extend(debug_info(&triple, &main, &crate_name));
+                files
             }
-            (a, c) => panic!("{a:?} is not compatible with {c:?}"),
-        }
+            (artifact, crate_type) => {
+                return Err(Error::IncompatibleCrateType {
+                    name: self.name.clone(),
+                    artifact,
+                    crate_type,
+                })
+            }
+        })
+    }
+}
+
+/// Separate debug information that `rustc` may produce next to the executable or dynamic
+/// library named `main`
+fn debug_info(triple: &Triple, main: &str, crate_name: &str) -> Option<ArtifactFile> {
+    let name = if triple.is_msvc() {
+        format!("{crate_name}.pdb")
+    } else if triple.is_apple() {
+        format!("{main}.dSYM")
+    } else if triple.supports_dwp() {
+        format!("{main}.dwp")
+    } else {
+        return None;
+    };
+    Some(ArtifactFile {
+        name,
+        flavor: FileFlavor::DebugInfo,
+    })
+}
+
+/// What a file in [`Artifact::file_names()`] is used for
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FileFlavor {
+    /// The artifact itself, or a file that is required to use it like the `.wasm` module
+    /// next to the `.js` loader of Emscripten executables
+    Normal,
+    /// An import library to link against a Windows DLL
+    Linkable,
+    /// Separate debug information, which is a directory for `.dSYM` bundles
+    DebugInfo,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ArtifactFile {
+    pub name: String,
+    pub flavor: FileFlavor,
+}
+
+#[test]
+fn test_declared_crate_types() {
+    let manifest = toml::from_str::<crate::manifest::Manifest>(
+        r#"
+[package]
+name = "foo"
+
+[[example]]
+name = "native-activity"
+crate-type = ["cdylib"]
+"#,
+    )
+    .unwrap();
+    let example = &manifest.examples[0];
+    let artifact = Artifact {
+        name: example.name.clone(),
+        path: PathBuf::from("examples/native-activity.rs"),
+        r#type: ArtifactType::Example,
+        crate_types: example.crate_type.clone().unwrap(),
+        harness: true,
+    };
+
+    assert_eq!(
+        artifact
+            .file_name(CrateType::Cdylib, "aarch64-linux-android")
+            .unwrap(),
+        "libnative_activity.so"
+    );
+    assert!(matches!(
+        artifact.file_name(CrateType::Bin, "aarch64-linux-android"),
+        Err(Error::UndeclaredCrateType { .. })
+    ));
+}
+
+#[test]
+fn test_crate_type_spelling() {
+    let lib = |manifest| {
+        toml::from_str::<crate::manifest::Manifest>(manifest)
+            .unwrap()
+            .lib
+            .unwrap()
+    };
+
+    let crate_types =
+        lib(r#"lib.crate-type = ["lib", "rlib", "dylib", "staticlib", "cdylib", "proc-macro"]"#)
+            .crate_type
+            .unwrap();
+    assert_eq!(
+        crate_types,
+        [
+            CrateType::Lib,
+            CrateType::Rlib,
+            CrateType::Dylib,
+            CrateType::Staticlib,
+            CrateType::Cdylib,
+            CrateType::ProcMacro
+        ]
+    );
+    assert!(lib("lib.proc-macro = true").proc_macro);
+
+    let artifact = Artifact {
+        name: "my-crate".to_owned(),
+        path: PathBuf::from("src/lib.rs"),
+        r#type: ArtifactType::Lib,
+        crate_types,
+        harness: true,
+    };
+    let file_name = |ty| artifact.file_name(ty, "x86_64-unknown-linux-gnu").unwrap();
+    assert_eq!(file_name(CrateType::Lib), "libmy_crate.rlib");
+    assert_eq!(file_name(CrateType::Rlib), "libmy_crate.rlib");
+    assert_eq!(file_name(CrateType::Dylib), "libmy_crate.so");
+    assert_eq!(file_name(CrateType::Staticlib), "libmy_crate.a");
+    assert_eq!(file_name(CrateType::Cdylib), "libmy_crate.so");
+    assert_eq!(file_name(CrateType::ProcMacro), "libmy_crate.so");
+
+    // `lib` and `rlib` are interchangeable when requesting a declared crate type
+    for (declared, requested) in [
+        (CrateType::Lib, CrateType::Rlib),
+        (CrateType::Rlib, CrateType::Lib),
+    ] {
+        let artifact = Artifact {
+            crate_types: vec![declared],
+            ..artifact.clone()
+        };
+        assert_eq!(
+            artifact
+                .file_name(requested, "x86_64-unknown-linux-gnu")
+                .unwrap(),
+            "libmy_crate.rlib"
+        );
+        assert!(matches!(
+            artifact.file_name(CrateType::Dylib, "x86_64-unknown-linux-gnu"),
+            Err(Error::UndeclaredCrateType { .. })
+        ));
     }
 }
+
+#[test]
+fn test_platform_file_names() {
+    let artifact = |r#type, crate_types| Artifact {
+        name: "my-crate".to_owned(),
+        path: PathBuf::new(),
+        r#type,
+        crate_types,
+        harness: true,
+    };
+    let bin = artifact(ArtifactType::Bin, vec![CrateType::Bin]);
+    let lib = artifact(
+        ArtifactType::Lib,
+        vec![CrateType::Staticlib, CrateType::Cdylib],
+    );
+    let names = |artifact: &Artifact, ty, target| {
+        artifact
+            .file_names(ty, target)
+            .unwrap()
+            .into_iter()
+            .map(|file| file.name)
+            .collect::<Vec<_>>()
+    };
+
+    let target = "aarch64-linux-android";
+    assert_eq!(
+        names(&bin, CrateType::Bin, target),
+        ["my-crate", "my-crate.dwp"]
+    );
+    assert_eq!(names(&lib, CrateType::Staticlib, target), ["libmy_crate.a"]);
+    assert_eq!(
+        names(&lib, CrateType::Cdylib, target),
+        ["libmy_crate.so", "libmy_crate.so.dwp"]
+    );
+
+    let target = "aarch64-apple-darwin";
+    assert_eq!(
+        names(&bin, CrateType::Bin, target),
+        ["my-crate", "my-crate.dSYM"]
+    );
+    assert_eq!(
+        names(&lib, CrateType::Cdylib, target),
+        ["libmy_crate.dylib", "libmy_crate.dylib.dSYM"]
+    );
+
+    let target = "x86_64-pc-windows-msvc";
+    assert_eq!(
+        names(&bin, CrateType::Bin, target),
+        ["my-crate.exe", "my_crate.pdb"]
+    );
+    assert_eq!(names(&lib, CrateType::Staticlib, target), ["my_crate.lib"]);
+    assert_eq!(
+        names(&lib, CrateType::Cdylib, target),
+        ["my_crate.dll", "my_crate.dll.lib", "my_crate.pdb"]
+    );
+
+    let target = "x86_64-pc-windows-gnu";
+    assert_eq!(names(&bin, CrateType::Bin, target), ["my-crate.exe"]);
+    assert_eq!(names(&lib, CrateType::Staticlib, target), ["libmy_crate.a"]);
+    assert_eq!(
+        names(&lib, CrateType::Cdylib, target),
+        ["my_crate.dll", "libmy_crate.dll.a"]
+    );
+
+    let target = "wasm32-unknown-unknown";
+    assert_eq!(names(&bin, CrateType::Bin, target), ["my-crate.wasm"]);
+    assert_eq!(names(&lib, CrateType::Cdylib, target), ["my_crate.wasm"]);
+
+    let target = "wasm32-unknown-emscripten";
+    assert_eq!(
+        names(&bin, CrateType::Bin, target),
+        ["my-crate.js", "my_crate.wasm"]
+    );
+}
@@ -0,0 +1,257 @@
+use crate::error::{Error, Result};
+use std::iter::Peekable;
+use std::process::Command;
+use std::str::{CharIndices, FromStr};
+
+/// A configuration option as printed by `rustc --print cfg`, which is either a
+/// plain name like `unix`, or a key-value pair like `target_os = "android"`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Cfg {
+    Name(String),
+    KeyPair(String, String),
+}
+
+impl FromStr for Cfg {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser::new(s);
+        let cfg = parser.parse_cfg()?;
+        parser.expect_end()?;
+        Ok(cfg)
+    }
+}
+
+/// A [conditional compilation] predicate as used in `target.'cfg(...)'` config tables.
+///
+/// [conditional compilation]: https://doc.rust-lang.org/reference/conditional-compilation.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CfgExpr {
+    Not(Box<CfgExpr>),
+    All(Vec<CfgExpr>),
+    Any(Vec<CfgExpr>),
+    Value(Cfg),
+}
+
+impl CfgExpr {
+    /// Parses a `cfg(...)` key of a [`target`] config table, or returns [`None`] when
+    /// `key` is not wrapped in `cfg()` (and hence names a target triple).
+    ///
+    /// [`target`]: https://doc.rust-lang.org/cargo/reference/config.html#target
+    pub fn from_target_key(key: &str) -> Option<Result<Self>> {
+        let expr = key.strip_prefix("cfg(")?.strip_suffix(')')?;
+        Some(expr.parse())
+    }
+
+    /// Evaluates this predicate against the options enabled for a target
+    pub fn matches(&self, cfgs: &[Cfg]) -> bool {
+        match self {
+            Self::Not(expr) => !expr.matches(cfgs),
+            Self::All(exprs) => exprs.iter().all(|expr| expr.matches(cfgs)),
+            Self::Any(exprs) => exprs.iter().any(|expr| expr.matches(cfgs)),
+            Self::Value(cfg) => cfgs.contains(cfg),
+        }
+    }
+}
+
+impl FromStr for CfgExpr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser::new(s);
+        let expr = parser.parse_expr()?;
+        parser.expect_end()?;
+        Ok(expr)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Token<'a> {
+    LeftParen,
+    RightParen,
+    Comma,
+    Equals,
+    Ident(&'a str),
+    String(&'a str),
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str) -> Self {
+        Self {
+            s,
+            chars: s.char_indices().peekable(),
+        }
+    }
+
+    fn error(&self, reason: &'static str) -> Error {
+        Error::InvalidCfg(self.s.to_owned(), reason)
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token<'a>>> {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let Some((start, c)) = self.chars.next() else {
+            return Ok(None);
+        };
+        Ok(Some(match c {
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            ',' => Token::Comma,
+            '=' => Token::Equals,
+            '"' => {
+                let (end, _) = self
+                    .chars
+                    .find(|&(_, c)| c == '"')
+                    .ok_or_else(|| self.error("unterminated string"))?;
+                Token::String(&self.s[start + 1..end])
+            }
+            c if c == '_' || c.is_alphabetic() => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) = self
+                    .chars
+                    .next_if(|&(_, c)| c == '_' || c.is_alphanumeric())
+                {
+                    end = i + c.len_utf8();
+                }
+                Token::Ident(&self.s[start..end])
+            }
+            _ => return Err(self.error("unexpected character")),
+        }))
+    }
+
+    fn peek_token(&self) -> Result<Option<Token<'a>>> {
+        Self {
+            s: self.s,
+            chars: self.chars.clone(),
+        }
+        .next_token()
+    }
+
+    fn expect_end(&mut self) -> Result<()> {
+        match self.next_token()? {
+            None => Ok(()),
+            Some(_) => Err(self.error("unexpected content after cfg expression")),
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<()> {
+        match self.next_token()? {
+            Some(t) if t == token => Ok(()),
+            _ => Err(self.error("unexpected token")),
+        }
+    }
+
+    fn parse_expr(&mut self) -> Result<CfgExpr> {
+        let op = match self.peek_token()? {
+            Some(Token::Ident(op @ ("all" | "any" | "not"))) => op,
+            _ => return self.parse_cfg().map(CfgExpr::Value),
+        };
+        self.next_token()?;
+        self.expect(Token::LeftParen)?;
+        let mut exprs = vec![];
+        loop {
+            if self.peek_token()? == Some(Token::RightParen) {
+                self.next_token()?;
+                break;
+            }
+            exprs.push(self.parse_expr()?);
+            match self.next_token()? {
+                Some(Token::Comma) => {}
+                Some(Token::RightParen) => break,
+                _ => return Err(self.error("expected `,` or `)`")),
+            }
+        }
+
+        Ok(match op {
+            "all" => CfgExpr::All(exprs),
+            "any" => CfgExpr::Any(exprs),
+            _ => match <[CfgExpr; 1]>::try_from(exprs) {
+                Ok([expr]) => CfgExpr::Not(Box::new(expr)),
+                Err(_) => return Err(self.error("`not()` takes exactly one predicate")),
+            },
+        })
+    }
+
+    fn parse_cfg(&mut self) -> Result<Cfg> {
+        let Some(Token::Ident(name)) = self.next_token()? else {
+            return Err(self.error("expected a cfg name"));
+        };
+        if self.peek_token()? != Some(Token::Equals) {
+            return Ok(Cfg::Name(name.to_owned()));
+        }
+        self.next_token()?;
+        match self.next_token()? {
+            Some(Token::String(value)) => Ok(Cfg::KeyPair(name.to_owned(), value.to_owned())),
+            _ => Err(self.error("expected a string after `=`")),
+        }
+    }
+}
+
+/// Queries the configuration options that `rustc` (or `$RUSTC`) enables for `target`,
+/// or for the host when no target is given.
+pub fn rustc_cfgs(target: Option<&str>) -> Result<Vec<Cfg>> {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let mut cmd = Command::new(rustc);
+    cmd.args(["--print", "cfg"]);
+    if let Some(target) = target {
+        cmd.arg("--target").arg(target);
+    }
+    let output = cmd.output().map_err(|_| Error::RustcNotFound)?;
+    if !output.status.success() {
+        return Err(Error::PrintCfg(
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::parse)
+        .collect()
+}
+
+#[test]
+fn test_cfg_expr() {
+    let cfgs = [
+        "unix",
+        "debug_assertions",
+        r#"target_os="android""#,
+        r#"target_arch="aarch64""#,
+    ]
+    .map(|cfg| cfg.parse::<Cfg>().unwrap());
+    assert_eq!(
+        cfgs[2],
+        Cfg::KeyPair("target_os".to_owned(), "android".to_owned())
+    );
+
+    let matches = |key: &str| {
+        CfgExpr::from_target_key(key)
+            .unwrap()
+            .unwrap()
+            .matches(&cfgs)
+    };
+    assert!(matches("cfg(unix)"));
+    assert!(!matches("cfg(windows)"));
+    assert!(matches(r#"cfg(target_os = "android")"#));
+    assert!(matches(
+        r#"cfg(all(target_arch = "aarch64", not(target_os = "linux")))"#
+    ));
+    assert!(matches(r#"cfg(any(windows, target_os = "android",))"#));
+    assert!(!matches("cfg(any())"));
+    assert!(matches("cfg(all())"));
+
+    assert!(CfgExpr::from_target_key("aarch64-linux-android").is_none());
+    for invalid in [
+        "cfg(not(unix, windows))",
+        "cfg(target_os = android)",
+        r#"cfg(target_os = "android)"#,
+        "cfg(unix windows)",
+    ] {
+        assert!(matches!(
+            CfgExpr::from_target_key(invalid).unwrap(),
+            Err(Error::InvalidCfg(..))
+        ));
+    }
+}
@@ -1,13 +1,15 @@
+use crate::cfg::{Cfg, CfgExpr};
 use crate::error::Error;
-use serde::Deserialize;
+use serde::{de::DeserializeOwned, Deserialize};
 use std::{
     borrow::Cow,
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     env::VarError,
     fmt::{self, Display, Formatter},
     io,
     ops::Deref,
     path::{Path, PathBuf},
+    process::Command,
 };
 
 /// Specific errors that can be raised during environment parsing
@@ -36,12 +38,39 @@ impl Display for EnvError {
 
 impl std::error::Error for EnvError {}
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "kebab-case")]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(try_from = "toml::value::Table")]
 pub struct Config {
     pub build: Option<Build>,
     /// <https://doc.rust-lang.org/cargo/reference/config.html#env>
     pub env: Option<BTreeMap<String, EnvOption>>,
+    /// `[target.<triple>]` and `[target.<cfg>]` tables, keyed by the triple or `cfg(...)`
+    /// expression, as per <https://doc.rust-lang.org/cargo/reference/config.html#target>
+    pub target: Option<BTreeMap<String, TargetConfig>>,
+    /// All values in the file, including those without a typed field above
+    raw: toml::value::Table,
+}
+
+/// The typed tables of a [`Config`]
+#[derive(Deserialize)]
+struct ConfigTables {
+    build: Option<Build>,
+    env: Option<BTreeMap<String, EnvOption>>,
+    target: Option<BTreeMap<String, TargetConfig>>,
+}
+
+impl TryFrom<toml::value::Table> for Config {
+    type Error = toml::de::Error;
+
+    fn try_from(raw: toml::value::Table) -> Result<Self, Self::Error> {
+        let ConfigTables { build, env, target } = toml::Value::Table(raw.clone()).try_into()?;
+        Ok(Self {
+            build,
+            env,
+            target,
+            raw,
+        })
+    }
 }
 
 impl Config {
@@ -49,6 +78,69 @@ impl Config {
         let contents = std::fs::read_to_string(path).map_err(|e| Error::Io(path.to_owned(), e))?;
         toml::from_str(&contents).map_err(|e| Error::Toml(path.to_owned(), e))
     }
+
+    /// The raw value at the dotted `key`, if any
+    fn raw_value(&self, key: &[&str]) -> Option<&toml::Value> {
+        let (last, tables) = key.split_last()?;
+        let mut table = &self.raw;
+        for part in tables {
+            table = table.get(*part)?.as_table()?;
+        }
+        table.get(*last)
+    }
+
+    /// The `[target.<key>]` table, where `key` is a triple or `cfg(...)` expression
+    fn target_config(&self, key: &str) -> Option<&TargetConfig> {
+        self.target.as_ref()?.get(key)
+    }
+}
+
+/// Where a config value was defined, to explain to the user how it was resolved
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Definition {
+    /// Defined in the config file at this path
+    Path(PathBuf),
+    /// Defined by the environment variable with this name
+    Environment(String),
+    /// Passed on the command line, via a `--config <path>` file if a path is set,
+    /// or an inline `--config KEY=VALUE` argument otherwise
+    Cli(Option<PathBuf>),
+    /// Passed to a dedicated command-line flag, like `--target-dir`
+    Flag(&'static str),
+}
+
+impl Definition {
+    /// The directory that relative paths in this value are resolved against: the parent of
+    /// the `.cargo` directory for config files, or `cwd` for the environment, inline
+    /// `--config` arguments and other command-line flags.
+    pub fn root<'a>(&'a self, cwd: &'a Path) -> &'a Path {
+        match self {
+            Self::Path(path) | Self::Cli(Some(path)) => path
+                .parent()
+                .and_then(Path::parent)
+                .unwrap_or(Path::new("")),
+            Self::Environment(_) | Self::Cli(None) | Self::Flag(_) => cwd,
+        }
+    }
+}
+
+impl Display for Definition {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "`{}`", path.display()),
+            Self::Environment(key) => write!(f, "environment variable `{key}`"),
+            Self::Cli(Some(path)) => write!(f, "`{}` (from --config cli option)", path.display()),
+            Self::Cli(None) => f.write_str("--config cli option"),
+            Self::Flag(flag) => write!(f, "`{flag}` flag"),
+        }
+    }
+}
+
+/// A resolved config value together with its [`Definition`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Value<T> {
+    pub val: T,
+    pub definition: Definition,
 }
 
 #[derive(Clone, Debug)]
@@ -56,6 +148,8 @@ pub struct LocalizedConfig {
     pub config: Config,
     /// The directory containing `./.cargo/config.toml`
     pub workspace: PathBuf,
+    /// Where this config was loaded from
+    pub definition: Definition,
 }
 
 impl Deref for LocalizedConfig {
@@ -67,51 +161,700 @@ impl Deref for LocalizedConfig {
 }
 
 impl LocalizedConfig {
+    /// Parses `.cargo/config.toml`, or the legacy `.cargo/config`, inside `workspace`.
     pub fn new(workspace: PathBuf) -> Result<Self, Error> {
+        let cargo_dir = workspace.join(".cargo");
+        let path = Self::find_config_file(&cargo_dir)?.unwrap_or(cargo_dir.join("config.toml"));
         Ok(Self {
-            config: Config::parse_from_toml(&workspace.join(".cargo/config.toml"))?,
+            config: Config::parse_from_toml(&path)?,
             workspace,
+            definition: Definition::Path(path),
+        })
+    }
+
+    /// Returns the config file inside a `.cargo` (or `CARGO_HOME`) `dir`, if any.
+    ///
+    /// Like cargo, the legacy extensionless `config` file is used when it exists (with a
+    /// deprecation warning), and takes precedence over `config.toml` when both exist as
+    /// separate files.
+    fn find_config_file(dir: &Path) -> Result<Option<PathBuf>, Error> {
+        let legacy = dir.join("config");
+        let path = dir.join("config.toml");
+
+        if !legacy.is_file() {
+            return Ok(path.is_file().then_some(path));
+        }
+
+        if path.is_file() {
+            // No need to warn when the legacy file is just a symlink to the new one
+            let canonicalize =
+                |path: &Path| dunce::canonicalize(path).map_err(|e| Error::Io(path.to_owned(), e));
+            if canonicalize(&legacy)? != canonicalize(&path)? {
+                eprintln!(
+                    "warning: both `{}` and `{}` exist. Using `{}`",
+                    legacy.display(),
+                    path.display(),
+                    legacy.display()
+                );
+            }
+        } else {
+            eprintln!(
+                "warning: `{}` is deprecated in favor of `config.toml`",
+                legacy.display()
+            );
+        }
+
+        Ok(Some(legacy))
+    }
+
+    /// Parses the config file at `path`, which is considered to live in a `.cargo`-like
+    /// directory regardless of its name: relative paths are resolved against the parent
+    /// of that directory.
+    fn from_path(path: &Path) -> Result<Self, Error> {
+        let definition = Definition::Path(path.to_owned());
+        Ok(Self {
+            config: Config::parse_from_toml(path)?,
+            workspace: definition.root(Path::new("")).to_owned(),
+            definition,
         })
     }
 
-    /// Search for `.cargo/config.toml` in any parent of the workspace root path.
-    /// Returns the directory which contains this path, not the path to the config file.
-    fn find_cargo_config_parent(workspace: impl AsRef<Path>) -> Result<Option<PathBuf>, Error> {
+    /// Search for `.cargo/config.toml` (or the legacy `.cargo/config`) in any parent of the
+    /// workspace root path.
+    fn find_cargo_config_path(workspace: impl AsRef<Path>) -> Result<Option<PathBuf>, Error> {
         let workspace = workspace.as_ref();
         let workspace =
             dunce::canonicalize(workspace).map_err(|e| Error::Io(workspace.to_owned(), e))?;
-        Ok(workspace
-            .ancestors()
-            .find(|dir| dir.join(".cargo/config.toml").is_file())
-            .map(|p| p.to_path_buf()))
+        for dir in workspace.ancestors() {
+            if let Some(path) = Self::find_config_file(&dir.join(".cargo"))? {
+                return Ok(Some(path));
+            }
+        }
+        Ok(None)
     }
 
-    /// Search for and open `.cargo/config.toml` in any parent of the workspace root path.
+    /// Search for and open `.cargo/config.toml` (or the legacy `.cargo/config`) in any parent
+    /// of the workspace root path.
+    ///
+    /// Only the config file closest to `workspace` is returned, use [`MergedConfig::find()`]
+    /// to take all config files in the hierarchy into account.
     pub fn find_cargo_config_for_workspace(
         workspace: impl AsRef<Path>,
     ) -> Result<Option<Self>, Error> {
-        let config = Self::find_cargo_config_parent(workspace)?;
-        config.map(Self::new).transpose()
+        let config = Self::find_cargo_config_path(workspace)?;
+        config.as_deref().map(Self::from_path).transpose()
     }
 
     /// Propagate environment variables from this `.cargo/config.toml` to the process environment
     /// using [`std::env::set_var()`].
     ///
-    /// Note that this is automatically performed when calling [`Subcommand::new()`][super::Subcommand::new()].
+    /// Prefer [`apply_env_vars()`][Self::apply_env_vars()] in multithreaded programs.
     pub fn set_env_vars(&self) -> Result<()> {
         if let Some(env) = &self.config.env {
             for (key, env_option) in env {
-                // Existing environment variables always have precedence unless
-                // the extended format is used to set `force = true`:
-                if !matches!(env_option, EnvOption::Value { force: true, .. })
-                    && std::env::var_os(key).is_some()
-                {
-                    continue;
+                env_option.set_env_var(key, &self.workspace)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets the environment variables from this `.cargo/config.toml` on `cmd`, without
+    /// touching the process environment.
+    pub fn apply_env_vars(&self, cmd: &mut Command) -> Result<()> {
+        if let Some(env) = &self.config.env {
+            for (key, env_option) in env {
+                env_option.apply_env_var(key, &self.workspace, cmd)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the [`CARGO_HOME`] directory: the `CARGO_HOME` environment variable if set
+/// (relative to the working directory), or `.cargo` in the home directory of the user.
+///
+/// [`CARGO_HOME`]: https://doc.rust-lang.org/cargo/guide/cargo-home.html
+pub fn cargo_home() -> Result<Option<PathBuf>, Error> {
+    match std::env::var_os("CARGO_HOME").filter(|home| !home.is_empty()) {
+        Some(cargo_home) => {
+            let cwd = std::env::current_dir().map_err(|e| Error::Io(PathBuf::new(), e))?;
+            Ok(Some(cwd.join(cargo_home)))
+        }
+        None => Ok(std::env::home_dir().map(|home| home.join(".cargo"))),
+    }
+}
+
+fn current_dir() -> Result<PathBuf, Error> {
+    std::env::current_dir().map_err(|e| Error::Io(PathBuf::new(), e))
+}
+
+/// Returns the name of the [environment variable] that overrides the config value at `key`,
+/// such as `CARGO_BUILD_TARGET_DIR` for `["build", "target-dir"]`.
+///
+/// [environment variable]: https://doc.rust-lang.org/cargo/reference/config.html#environment-variables
+pub fn env_key(key: &[&str]) -> String {
+    let mut env_key = String::from("CARGO");
+    for part in key {
+        env_key.push('_');
+        env_key.extend(part.chars().map(|c| match c {
+            '-' | '.' => '_',
+            c => c.to_ascii_uppercase(),
+        }));
+    }
+    env_key
+}
+
+/// Reads and deserializes the [environment variable][env_key()] that overrides the config
+/// value at `key`.  Like cargo, `true`/`false` and integers are interpreted as such when
+/// `T` requires it, and lists can be given as whitespace-separated strings.
+fn env_var<T: DeserializeOwned>(key: &[&str]) -> Result<Option<T>, Error> {
+    let env_key = env_key(key);
+    let value = match std::env::var(&env_key) {
+        Ok(value) => value,
+        Err(VarError::NotPresent) => return Ok(None),
+        Err(e) => return Err(Error::Env(e.into())),
+    };
+
+    let typed = match value.as_str() {
+        "true" => toml::Value::Boolean(true),
+        "false" => toml::Value::Boolean(false),
+        value => value.parse().map_or_else(
+            |_| toml::Value::String(value.to_owned()),
+            toml::Value::Integer,
+        ),
+    };
+    let list = toml::Value::Array(
+        value
+            .split_whitespace()
+            .map(|item| toml::Value::String(item.to_owned()))
+            .collect(),
+    );
+
+    typed
+        .try_into()
+        .or_else(|_| toml::Value::String(value).try_into())
+        .or_else(|e| list.try_into().map_err(|_| e))
+        .map(Some)
+        .map_err(|e| Error::ConfigEnvVar(env_key, e))
+}
+
+/// Returns the first value that `get` finds in `configs`, ordered by precedence
+fn find<T: Clone>(
+    configs: &[LocalizedConfig],
+    get: impl Fn(&Config) -> Option<&T>,
+) -> Option<Value<T>> {
+    configs.iter().find_map(|config| {
+        Some(Value {
+            val: get(&config.config)?.clone(),
+            definition: config.definition.clone(),
+        })
+    })
+}
+
+/// Returns all values that `get` finds in `configs`, ordered from lowest to highest precedence
+fn find_all<T: Clone>(
+    configs: &[LocalizedConfig],
+    get: impl Fn(&Config) -> Option<&T>,
+) -> Vec<Value<T>> {
+    configs
+        .iter()
+        .rev()
+        .filter_map(|config| {
+            Some(Value {
+                val: get(&config.config)?.clone(),
+                definition: config.definition.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Returns the first raw value at `key` in `configs`, ordered by precedence
+fn find_raw<'a>(
+    configs: &'a [LocalizedConfig],
+    key: &[&str],
+) -> Option<(&'a toml::Value, &'a Definition)> {
+    configs
+        .iter()
+        .find_map(|config| Some((config.raw_value(key)?, &config.definition)))
+}
+
+/// Merges the list `values` at `key`, ordered from lowest to highest precedence, like cargo:
+/// arrays are concatenated, whereas a string replaces a lower-precedence string and it is
+/// an error to mix both.
+fn merge_list(
+    key: &[&str],
+    values: Vec<Value<StringOrArray>>,
+) -> Result<Vec<Value<StringOrArray>>, Error> {
+    let mut merged: Vec<Value<StringOrArray>> = vec![];
+    for value in values {
+        let lower = merged.last();
+        match (lower.map(|lower| &lower.val), &value.val) {
+            (None, _) | (Some(StringOrArray::Array(_)), StringOrArray::Array(_)) => {}
+            (Some(StringOrArray::String(_)), StringOrArray::String(_)) => merged.clear(),
+            (Some(_), _) => {
+                return Err(Error::ConfigMerge {
+                    key: key.join("."),
+                    lower: Box::new(lower.unwrap().definition.clone()),
+                    higher: Box::new(value.definition),
+                })
+            }
+        }
+        merged.push(value);
+    }
+    Ok(merged)
+}
+
+/// Splits an inline `--config` argument at its first unquoted `=` into the key and value
+/// expressions
+fn split_key_value(arg: &str) -> Option<(&str, &str)> {
+    let mut quote = None;
+    for (i, c) in arg.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '=') => return Some((&arg[..i], &arg[i + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Whether `value` is a chain of tables with exactly one key each, ending in a non-table
+/// value, as parsed from a dotted `KEY=VALUE` expression like `build.target-dir="out"`.
+fn is_single_key(value: &toml::Value) -> bool {
+    match value.as_table() {
+        Some(table) => match table.values().next() {
+            Some(value) if table.len() == 1 => is_single_key(value),
+            _ => false,
+        },
+        None => true,
+    }
+}
+
+/// Cargo configuration from all `.cargo/config.toml` files that apply to a directory,
+/// merged following cargo's [hierarchical structure]: values from files in deeper
+/// directories take precedence over values from files in their parents and tables are
+/// merged per key.  When the lists in `build.target` and the various `rustflags` are
+/// given as arrays they are concatenated instead, starting with the lowest precedence,
+/// whereas a `runner` is always taken as a whole.
+///
+/// [hierarchical structure]: https://doc.rust-lang.org/cargo/reference/config.html#hierarchical-structure
+#[derive(Clone, Debug, Default)]
+pub struct MergedConfig {
+    /// Ordered from highest to lowest precedence, starting with `--config` arguments and
+    /// ending with the [`cargo_home()`] config
+    configs: Vec<LocalizedConfig>,
+    /// The number of `--config` arguments at the start of `configs`
+    cli_args: usize,
+}
+
+impl MergedConfig {
+    /// Search for and open `.cargo/config.toml` (or the legacy `.cargo/config`) in `dir` and
+    /// all of its parents, followed by the config in [`cargo_home()`] at the lowest precedence.
+    pub fn find(dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let dir = dir.as_ref();
+        let dir = dunce::canonicalize(dir).map_err(|e| Error::Io(dir.to_owned(), e))?;
+        // The home config is only read once, even if it lives in one of the parent directories.
+        // Only the directory is resolved, so that a symlinked config file keeps its own path.
+        let cargo_home = cargo_home()?.filter(|cargo_home| cargo_home.is_dir());
+        let canonical_home = cargo_home
+            .as_deref()
+            .map(|cargo_home| {
+                dunce::canonicalize(cargo_home).map_err(|e| Error::Io(cargo_home.to_owned(), e))
+            })
+            .transpose()?;
+
+        let mut paths = vec![];
+        let mut home_in_ancestors = false;
+        for dir in dir.ancestors() {
+            let cargo_dir = dir.join(".cargo");
+            home_in_ancestors |= canonical_home.as_ref() == Some(&cargo_dir);
+            paths.extend(LocalizedConfig::find_config_file(&cargo_dir)?);
+        }
+
+        if let Some(cargo_home) = cargo_home.filter(|_| !home_in_ancestors) {
+            paths.extend(LocalizedConfig::find_config_file(&cargo_home)?);
+        }
+
+        let configs = paths
+            .iter()
+            .map(|path| LocalizedConfig::from_path(path))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            configs,
+            cli_args: 0,
+        })
+    }
+
+    /// Layers the [`--config`] command-line arguments on top of all config files, with the
+    /// last argument taking the highest precedence.  Each argument is either a path to an
+    /// existing config file, or an inline `KEY=VALUE` TOML expression such as
+    /// `build.target-dir="out"`, whose relative paths are resolved against `cwd`.
+    ///
+    /// [`--config`]: https://doc.rust-lang.org/cargo/reference/config.html#command-line-overrides
+    pub fn with_cli_args(mut self, args: &[String], cwd: &Path) -> Result<Self, Error> {
+        for arg in args {
+            let path = cwd.join(arg);
+            let config = if !arg.is_empty() && path.is_file() {
+                LocalizedConfig {
+                    definition: Definition::Cli(Some(path.clone())),
+                    ..LocalizedConfig::from_path(&path)?
+                }
+            } else {
+                let error = |e| Error::ConfigArg(arg.to_owned(), e);
+                let value = toml::from_str::<toml::Value>(arg).map_err(error)?;
+                // Once parsed, table headers and inline tables are indistinguishable from
+                // dotted keys, so those are rejected based on the expression itself
+                let is_dotted_key = split_key_value(arg).is_some_and(|(key, value)| {
+                    !key.trim_start().starts_with('[') && !value.trim_start().starts_with('{')
+                });
+                if !is_dotted_key || !is_single_key(&value) {
+                    return Err(error(serde::de::Error::custom(
+                        "expected a single dotted `KEY=VALUE` expression that does not set a table",
+                    )));
+                }
+                LocalizedConfig {
+                    config: value.try_into().map_err(error)?,
+                    workspace: cwd.to_owned(),
+                    definition: Definition::Cli(None),
+                }
+            };
+            self.configs.insert(0, config);
+            self.cli_args += 1;
+        }
+        Ok(self)
+    }
+
+    /// All config files and `--config` arguments that were loaded, ordered from highest to
+    /// lowest precedence
+    pub fn configs(&self) -> &[LocalizedConfig] {
+        &self.configs
+    }
+
+    /// Deserializes the value at the dotted `key`, such as `["profile", "release", "lto"]`,
+    /// with the highest precedence: `--config` arguments first, then the
+    /// [environment variable][env_key()] for `key`, and finally the config files.
+    ///
+    /// Tables are not merged and relative paths are not resolved; use the
+    /// [root][Definition::root()] of the returned definition for the latter.
+    pub fn get<T: DeserializeOwned>(&self, key: &[&str]) -> Result<Option<Value<T>>, Error> {
+        let (cli_args, files) = self.configs.split_at(self.cli_args);
+        let (raw, definition) = match find_raw(cli_args, key) {
+            Some(raw) => raw,
+            None => {
+                if let Some(val) = env_var(key)? {
+                    return Ok(Some(Value {
+                        val,
+                        definition: Definition::Environment(env_key(key)),
+                    }));
+                }
+                match find_raw(files, key) {
+                    Some(raw) => raw,
+                    None => return Ok(None),
+                }
+            }
+        };
+        let val = raw.clone().try_into().map_err(|error| Error::ConfigValue {
+            key: key.join("."),
+            definition: Box::new(definition.clone()),
+            error,
+        })?;
+        Ok(Some(Value {
+            val,
+            definition: definition.clone(),
+        }))
+    }
+
+    /// Like [`get()`][Self::get()], but reads the typed field that `get` finds in each
+    /// config instead of deserializing the raw value.
+    fn get_field<T: Clone + DeserializeOwned>(
+        &self,
+        key: &[&str],
+        get: impl Fn(&Config) -> Option<&T>,
+    ) -> Result<Option<Value<T>>, Error> {
+        let (cli_args, files) = self.configs.split_at(self.cli_args);
+
+        if let Some(value) = find(cli_args, &get) {
+            return Ok(Some(value));
+        }
+        if let Some(val) = env_var(key)? {
+            return Ok(Some(Value {
+                val,
+                definition: Definition::Environment(env_key(key)),
+            }));
+        }
+        Ok(find(files, &get))
+    }
+
+    /// Returns the value of the [environment variable][env_key()] for the list at `key`,
+    /// which overrides the config like a string does, or else all values that remain after
+    /// [merging][merge_list()] the config files and `--config` arguments, ordered from
+    /// lowest to highest precedence.
+    fn get_list(
+        &self,
+        key: &[&str],
+        get: impl Fn(&Config) -> Option<&StringOrArray>,
+    ) -> Result<Vec<Value<StringOrArray>>, Error> {
+        if let Some(val) = env_var(key)? {
+            return Ok(vec![Value {
+                val,
+                definition: Definition::Environment(env_key(key)),
+            }]);
+        }
+        merge_list(key, find_all(&self.configs, get))
+    }
+
+    /// The [`build.target-dir`], made absolute against the [root][Definition::root()] of
+    /// its definition.
+    ///
+    /// [`build.target-dir`]: https://doc.rust-lang.org/cargo/reference/config.html#buildtarget-dir
+    pub fn target_dir(&self) -> Result<Option<Value<PathBuf>>, Error> {
+        let cwd = current_dir()?;
+        Ok(self
+            .get_field(&["build", "target-dir"], |config| {
+                config.build.as_ref()?.target_dir.as_ref()
+            })?
+            .map(|Value { val, definition }| Value {
+                val: definition.root(&cwd).join(val),
+                definition,
+            }))
+    }
+
+    /// The [`build.target`] triples to build for by default, with the lowest precedence first
+    /// when arrays from multiple config files are concatenated.  Paths to [custom target] `.json` files are made absolute
+    /// against the [root][Definition::root()] of their definition.
+    ///
+    /// [`build.target`]: https://doc.rust-lang.org/cargo/reference/config.html#buildtarget
+    /// [custom target]: https://doc.rust-lang.org/rustc/targets/custom.html
+    pub fn build_target(&self) -> Result<Vec<Value<String>>, Error> {
+        let cwd = current_dir()?;
+        Ok(self
+            .get_list(&["build", "target"], |config| {
+                config.build.as_ref()?.target.as_ref()
+            })?
+            .into_iter()
+            .flat_map(|Value { val, definition }| {
+                val.as_slice()
+                    .iter()
+                    .map(|target| Value {
+                        val: if target.ends_with(".json") {
+                            definition.root(&cwd).join(target).display().to_string()
+                        } else {
+                            target.clone()
+                        },
+                        definition: definition.clone(),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect())
+    }
+
+    /// The flags configured in [`build.rustflags`] by all config files, lowest precedence
+    /// first. See [`Subcommand::rustflags()`] for the
+    /// flags that cargo passes to `rustc`, including from other sources.
+    ///
+    /// [`build.rustflags`]: https://doc.rust-lang.org/cargo/reference/config.html#buildrustflags
+    /// [`Subcommand::rustflags()`]: crate::Subcommand::rustflags()
+    pub fn build_rustflags(&self) -> Result<Vec<Value<Vec<String>>>, Error> {
+        Ok(self
+            .get_list(&["build", "rustflags"], |config| {
+                config.build.as_ref()?.rustflags.as_ref()
+            })?
+            .into_iter()
+            .map(|Value { val, definition }| Value {
+                val: val.to_args(),
+                definition,
+            })
+            .collect())
+    }
+
+    /// The flags configured in [`build.rustdocflags`] by all config files, lowest precedence
+    /// first
+    ///
+    /// [`build.rustdocflags`]: https://doc.rust-lang.org/cargo/reference/config.html#buildrustdocflags
+    pub fn build_rustdocflags(&self) -> Result<Vec<Value<Vec<String>>>, Error> {
+        Ok(self
+            .get_list(&["build", "rustdocflags"], |config| {
+                config.build.as_ref()?.rustdocflags.as_ref()
+            })?
+            .into_iter()
+            .map(|Value { val, definition }| Value {
+                val: val.to_args(),
+                definition,
+            })
+            .collect())
+    }
+
+    /// Resolves the [`target`] tables that apply to `triple`, whose configuration options
+    /// as reported by [`rustc --print cfg`][crate::rustc_cfgs()] are given in `cfgs`.
+    ///
+    /// Like cargo, `target.<triple>` settings take precedence over those from matching
+    /// `target.<cfg>` tables, and it is an error for multiple `target.<cfg>` tables to
+    /// provide the same `linker` or `runner`.  The `rustflags` of all matching tables
+    /// are joined, after merging those of each table across all config files like the
+    /// other lists in [`MergedConfig`].
+    ///
+    /// [`target`]: https://doc.rust-lang.org/cargo/reference/config.html#target
+    pub fn target(&self, triple: &str, cfgs: &[Cfg]) -> Result<ResolvedTarget, Error> {
+        let cwd = current_dir()?;
+
+        let mut cfg_keys = BTreeSet::new();
+        for config in &self.configs {
+            for key in config.target.iter().flat_map(BTreeMap::keys) {
+                if let Some(expr) = CfgExpr::from_target_key(key) {
+                    if expr?.matches(cfgs) {
+                        cfg_keys.insert(key.as_str());
+                    }
                 }
+            }
+        }
+
+        // Executables without a path separator are looked up in `$PATH` instead
+        let executable = |path: &str, definition: &Definition| {
+            let path = Path::new(path);
+            if path.components().count() > 1 {
+                definition.root(&cwd).join(path)
+            } else {
+                path.to_owned()
+            }
+        };
+
+        let linker = match self.get_field(&["target", triple, "linker"], |config| {
+            config.target_config(triple)?.linker.as_ref()
+        })? {
+            Some(linker) => Some(linker),
+            None => self.cfg_value(&cfg_keys, "linker", |target| target.linker.as_ref())?,
+        }
+        .map(|Value { val, definition }| Value {
+            val: executable(&val, &definition),
+            definition,
+        });
+
+        let runner = match self.get_field(&["target", triple, "runner"], |config| {
+            config.target_config(triple)?.runner.as_ref()
+        })? {
+            Some(runner) => Some(runner),
+            None => self.cfg_value(&cfg_keys, "runner", |target| target.runner.as_ref())?,
+        }
+        .and_then(|Value { val, definition }| {
+            let args = val.to_args();
+            let (path, args) = args.split_first()?;
+            Some(Value {
+                val: PathAndArgs {
+                    path: executable(path, &definition),
+                    args: args.to_vec(),
+                },
+                definition,
+            })
+        });
+
+        let mut rustflags = self.get_list(&["target", triple, "rustflags"], |config| {
+            config.target_config(triple)?.rustflags.as_ref()
+        })?;
+        for key in &cfg_keys {
+            let values = find_all(&self.configs, |config| {
+                config.target_config(key)?.rustflags.as_ref()
+            });
+            rustflags.extend(merge_list(&["target", key, "rustflags"], values)?);
+        }
+        let rustflags = rustflags
+            .into_iter()
+            .map(|Value { val, definition }| Value {
+                val: val.to_args(),
+                definition,
+            })
+            .collect();
+
+        let ar = self
+            .get_field(&["target", triple, "ar"], |config| {
+                config.target_config(triple)?.ar.as_ref()
+            })?
+            .map(|Value { val, definition }| Value {
+                val: executable(&val, &definition),
+                definition,
+            });
+
+        Ok(ResolvedTarget {
+            linker,
+            runner,
+            rustflags,
+            ar,
+        })
+    }
+
+    /// Returns the value of `field` from the only `target.<cfg>` table in `cfg_keys` that
+    /// provides it, erroring if several tables do.
+    fn cfg_value<T: Clone>(
+        &self,
+        cfg_keys: &BTreeSet<&str>,
+        field: &'static str,
+        get: impl Fn(&TargetConfig) -> Option<&T>,
+    ) -> Result<Option<Value<T>>, Error> {
+        let mut matches = cfg_keys.iter().filter_map(|key| {
+            let value = find(&self.configs, |config| get(config.target_config(key)?))?;
+            Some((key, value))
+        });
 
-                std::env::set_var(key, env_option.resolve_value(&self.workspace)?.as_ref())
+        let first = matches.next();
+        if let (Some((first_key, first)), Some((second_key, second))) = (&first, matches.next()) {
+            return Err(Error::AmbiguousCfgTarget {
+                field,
+                first: Box::new(Value {
+                    val: first_key.to_string(),
+                    definition: first.definition.clone(),
+                }),
+                second: Box::new(Value {
+                    val: second_key.to_string(),
+                    definition: second.definition,
+                }),
+            });
+        }
+        Ok(first.map(|(_, value)| value))
+    }
+
+    /// All variables in [`env`], each mapped to the value with the highest precedence.
+    ///
+    /// [`env`]: https://doc.rust-lang.org/cargo/reference/config.html#env
+    pub fn env(&self) -> BTreeMap<&str, Value<&EnvOption>> {
+        let mut env = BTreeMap::new();
+        for config in &self.configs {
+            for (key, env_option) in config.env.iter().flatten() {
+                env.entry(key.as_str()).or_insert_with(|| Value {
+                    val: env_option,
+                    definition: config.definition.clone(),
+                });
             }
         }
+        env
+    }
+
+    /// Propagate all merged [`env()`][Self::env()] variables to the process environment
+    /// using [`std::env::set_var()`].
+    ///
+    /// Modifying the process environment is racy in multithreaded programs, hence this is
+    /// opt-in: prefer [`apply_env_vars()`][Self::apply_env_vars()] to only pass the
+    /// variables to child processes.
+    pub fn set_env_vars(&self) -> Result<()> {
+        let cwd = std::env::current_dir().map_err(|e| EnvError::Io(PathBuf::new(), e))?;
+        for (key, Value { val, definition }) in self.env() {
+            val.set_env_var(key, definition.root(&cwd))?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets all merged [`env()`][Self::env()] variables on `cmd`, like cargo does for the
+    /// processes it runs.  Variables that are already set on `cmd` or in the process
+    /// environment are kept unless [`EnvOption::Value::force`] is set, and
+    /// [`EnvOption::Value::relative`] values are resolved against the
+    /// [root][Definition::root()] of their definition.
+    pub fn apply_env_vars(&self, cmd: &mut Command) -> Result<()> {
+        let cwd = std::env::current_dir().map_err(|e| EnvError::Io(PathBuf::new(), e))?;
+        for (key, Value { val, definition }) in self.env() {
+            val.apply_env_var(key, definition.root(&cwd), cmd)?;
+        }
 
         Ok(())
     }
@@ -121,6 +864,64 @@ impl LocalizedConfig {
 #[serde(rename_all = "kebab-case")]
 pub struct Build {
     pub target_dir: Option<String>,
+    pub target: Option<StringOrArray>,
+    pub rustflags: Option<StringOrArray>,
+    pub rustdocflags: Option<StringOrArray>,
+}
+
+/// Settings for a target in [`Config::target`], of which the relevant ones are resolved
+/// by [`MergedConfig::target()`]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct TargetConfig {
+    pub linker: Option<String>,
+    pub runner: Option<StringOrArray>,
+    pub rustflags: Option<StringOrArray>,
+    pub ar: Option<String>,
+}
+
+/// The settings from all `[target]` tables that apply to a target
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResolvedTarget {
+    pub linker: Option<Value<PathBuf>>,
+    pub runner: Option<Value<PathAndArgs>>,
+    /// Flags from `target.<triple>.rustflags` followed by those of every matching
+    /// `target.<cfg>` table, each merged across all config files with the lowest
+    /// precedence first
+    pub rustflags: Vec<Value<Vec<String>>>,
+    pub ar: Option<Value<PathBuf>>,
+}
+
+/// An executable together with the arguments to pass to it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathAndArgs {
+    pub path: PathBuf,
+    pub args: Vec<String>,
+}
+
+/// A config value that may be specified either as a single string or as an array of strings
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum StringOrArray {
+    String(String),
+    Array(Vec<String>),
+}
+
+impl StringOrArray {
+    pub fn as_slice(&self) -> &[String] {
+        match self {
+            Self::String(value) => std::slice::from_ref(value),
+            Self::Array(values) => values,
+        }
+    }
+
+    /// The values as a list of arguments, where a single string is split on whitespace
+    pub fn to_args(&self) -> Vec<String> {
+        match self {
+            Self::String(value) => value.split_whitespace().map(str::to_owned).collect(),
+            Self::Array(values) => values.clone(),
+        }
+    }
 }
 
 /// Serializable environment variable in cargo config, configurable as per
@@ -160,6 +961,43 @@ impl EnvOption {
             Self::String(value) | Self::Value { value, .. } => value.into(),
         })
     }
+
+    /// Sets `key` to the [resolved][Self::resolve_value()] value in the process environment,
+    /// unless it is already set and [`EnvOption::Value::force`] is not.
+    fn set_env_var(&self, key: &str, config_parent: impl AsRef<Path>) -> Result<()> {
+        // Existing environment variables always have precedence unless
+        // the extended format is used to set `force = true`:
+        if !matches!(self, Self::Value { force: true, .. }) && std::env::var_os(key).is_some() {
+            return Ok(());
+        }
+
+        std::env::set_var(key, self.resolve_value(config_parent)?.as_ref());
+        Ok(())
+    }
+
+    /// Sets `key` to the [resolved][Self::resolve_value()] value on `cmd`, unless it is
+    /// already set on `cmd` or inherited from the process environment, and
+    /// [`EnvOption::Value::force`] is not set.
+    fn apply_env_var(
+        &self,
+        key: &str,
+        config_parent: impl AsRef<Path>,
+        cmd: &mut Command,
+    ) -> Result<()> {
+        if !matches!(self, Self::Value { force: true, .. }) {
+            let is_set = match cmd.get_envs().find(|(k, _)| *k == key) {
+                // Explicitly set or removed on `cmd`
+                Some((_, value)) => value.is_some(),
+                None => std::env::var_os(key).is_some(),
+            };
+            if is_set {
+                return Ok(());
+            }
+        }
+
+        cmd.env(key, self.resolve_value(config_parent)?.as_ref());
+        Ok(())
+    }
 }
 
 #[test]
@@ -195,13 +1033,10 @@ ENV_VAR_NAME_3 = { value = "relative/path", relative = true }"#;
         },
     );
 
-    assert_eq!(
-        toml::from_str::<Config>(toml),
-        Ok(Config {
-            build: None,
-            env: Some(env)
-        })
-    );
+    let config = toml::from_str::<Config>(toml).unwrap();
+    assert_eq!(config.build, None);
+    assert_eq!(config.env, Some(env));
+    assert_eq!(config.target, None);
 }
 
 #[test]
@@ -214,6 +1049,7 @@ CARGO_SUBCOMMAND_TEST_ENV_FORCED = { value = "forced", force = true }"#;
     let config = LocalizedConfig {
         config: toml::from_str::<Config>(toml).unwrap(),
         workspace: PathBuf::new(),
+        definition: Definition::Path(PathBuf::from(".cargo/config.toml")),
     };
 
     // Check if all values are propagated to the environment
@@ -270,6 +1106,7 @@ CARGO_SUBCOMMAND_TEST_ENV_SRC_DIR = { value = "src", force = true, relative = tr
     let config = LocalizedConfig {
         config: toml::from_str::<Config>(toml).unwrap(),
         workspace: PathBuf::new(),
+        definition: Definition::Path(PathBuf::from(".cargo/config.toml")),
     };
 
     config.set_env_vars().unwrap();
@@ -289,7 +1126,627 @@ CARGO_SUBCOMMAND_TEST_ENV_INEXISTENT_DIR = { value = "blahblahthisfolderdoesntex
     let config = LocalizedConfig {
         config: toml::from_str::<Config>(toml).unwrap(),
         workspace: PathBuf::new(),
+        definition: Definition::Path(PathBuf::from(".cargo/config.toml")),
     };
 
     assert!(matches!(config.set_env_vars(), Err(EnvError::Io(..))));
 }
+
+#[test]
+fn test_merged_config_precedence() {
+    let parent = LocalizedConfig {
+        config: toml::from_str::<Config>(
+            r#"
+[build]
+target-dir = "parent-target"
+
+[env]
+FROM_PARENT = "parent"
+OVERRIDDEN = "parent"
+"#,
+        )
+        .unwrap(),
+        workspace: PathBuf::from("/parent"),
+        definition: Definition::Path(PathBuf::from("/parent/.cargo/config.toml")),
+    };
+    let child = LocalizedConfig {
+        config: toml::from_str::<Config>(
+            r#"
+[env]
+OVERRIDDEN = "child"
+"#,
+        )
+        .unwrap(),
+        workspace: PathBuf::from("/parent/child"),
+        definition: Definition::Path(PathBuf::from("/parent/child/.cargo/config.toml")),
+    };
+
+    let config = MergedConfig {
+        configs: vec![child, parent],
+        cli_args: 0,
+    };
+
+    // Relative to the directory containing the `.cargo` folder that defines it
+    assert_eq!(
+        config.target_dir().unwrap(),
+        Some(Value {
+            val: PathBuf::from("/parent/parent-target"),
+            definition: Definition::Path(PathBuf::from("/parent/.cargo/config.toml"))
+        })
+    );
+
+    let env = config.env();
+    assert_eq!(env.len(), 2);
+    assert_eq!(env["FROM_PARENT"].val, &EnvOption::String("parent".into()));
+    assert_eq!(env["OVERRIDDEN"].val, &EnvOption::String("child".into()));
+    assert_eq!(
+        env["OVERRIDDEN"].definition,
+        Definition::Path(PathBuf::from("/parent/child/.cargo/config.toml"))
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_legacy_config_file() {
+    let dir = crate::utils::create_test_dir(
+        "legacy-config-file",
+        &[
+            ("legacy/.cargo/config", "build.target-dir = \"legacy\""),
+            ("both/.cargo/config", "build.target-dir = \"legacy\""),
+            ("both/.cargo/config.toml", "build.target-dir = \"toml\""),
+            ("symlink/.cargo/config.toml", "build.target-dir = \"toml\""),
+            ("toml/.cargo/config.toml", "build.target-dir = \"toml\""),
+            ("none/.cargo/other.toml", ""),
+        ],
+    );
+    std::os::unix::fs::symlink("config.toml", dir.join("symlink/.cargo/config")).unwrap();
+
+    let find = |name: &str| LocalizedConfig::find_config_file(&dir.join(name).join(".cargo"));
+    assert_eq!(
+        find("legacy").unwrap(),
+        Some(dir.join("legacy/.cargo/config"))
+    );
+    // Like cargo, the legacy file takes precedence when both exist
+    assert_eq!(find("both").unwrap(), Some(dir.join("both/.cargo/config")));
+    assert_eq!(
+        LocalizedConfig::new(dir.join("both"))
+            .unwrap()
+            .build
+            .as_ref()
+            .unwrap()
+            .target_dir
+            .as_deref(),
+        Some("legacy")
+    );
+    assert_eq!(
+        find("symlink").unwrap(),
+        Some(dir.join("symlink/.cargo/config"))
+    );
+    assert_eq!(
+        find("toml").unwrap(),
+        Some(dir.join("toml/.cargo/config.toml"))
+    );
+    assert_eq!(find("none").unwrap(), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cargo_home_config() {
+    let dir = std::env::temp_dir().join("cargo-subcommand-test-cargo-home");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("home")).unwrap();
+    std::fs::create_dir_all(dir.join("ws/.cargo")).unwrap();
+    std::fs::create_dir_all(dir.join("ws/sub")).unwrap();
+    std::fs::write(dir.join("real.toml"), "build.target-dir = \"home-target\"").unwrap();
+    std::fs::write(dir.join("ws/.cargo/config.toml"), "").unwrap();
+    std::os::unix::fs::symlink("../real.toml", dir.join("home/config.toml")).unwrap();
+    std::os::unix::fs::symlink("ws/.cargo", dir.join("ws-home")).unwrap();
+    let dir = dunce::canonicalize(dir).unwrap();
+
+    // A symlinked config file keeps the path (and hence the root) of the home directory
+    std::env::set_var("CARGO_HOME", dir.join("home"));
+    let config = MergedConfig::find(dir.join("ws/sub")).unwrap();
+    let definitions = config
+        .configs()
+        .iter()
+        .map(|config| &config.definition)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        definitions,
+        [
+            &Definition::Path(dir.join("ws/.cargo/config.toml")),
+            &Definition::Path(dir.join("home/config.toml")),
+        ]
+    );
+    assert_eq!(
+        config.target_dir().unwrap().unwrap().val,
+        dir.join("home-target")
+    );
+
+    // A symlinked home directory that is also one of the parents is only read once
+    std::env::set_var("CARGO_HOME", dir.join("ws-home"));
+    let config = MergedConfig::find(dir.join("ws/sub")).unwrap();
+    assert_eq!(config.configs().len(), 1);
+}
+
+#[test]
+fn test_cli_args() {
+    let cwd = current_dir().unwrap();
+    let args = [
+        "build.target-dir = \"out\"".to_owned(),
+        "env.FOO.value = \"bar\"".to_owned(),
+        "env.\"A=B\" = \"{}\"".to_owned(),
+    ];
+    let config = MergedConfig::default().with_cli_args(&args, &cwd).unwrap();
+    assert_eq!(
+        config.target_dir().unwrap(),
+        Some(Value {
+            val: cwd.join("out"),
+            definition: Definition::Cli(None),
+        })
+    );
+    assert_eq!(config.env().len(), 2);
+
+    for invalid in [
+        "[build]",
+        "build.target-dir = \"out\"\nbuild.target = \"x86_64-unknown-linux-gnu\"",
+        "build = {}",
+        "env.FOO = { value = \"x\" }",
+        "[build]\ntarget-dir = \"x\"",
+        "",
+    ] {
+        assert!(matches!(
+            MergedConfig::default().with_cli_args(&[invalid.to_owned()], &cwd),
+            Err(Error::ConfigArg(..))
+        ));
+    }
+}
+
+#[test]
+fn test_apply_env_vars() {
+    let toml = r#"
+[env]
+CARGO_SUBCOMMAND_TEST_APPLY_NEW = "new"
+CARGO_SUBCOMMAND_TEST_APPLY_INHERITED = "config"
+CARGO_SUBCOMMAND_TEST_APPLY_CMD = "config"
+CARGO_SUBCOMMAND_TEST_APPLY_FORCED = { value = "forced", force = true }"#;
+
+    let config = MergedConfig {
+        configs: vec![LocalizedConfig {
+            config: toml::from_str::<Config>(toml).unwrap(),
+            workspace: PathBuf::new(),
+            definition: Definition::Path(PathBuf::from(".cargo/config.toml")),
+        }],
+        cli_args: 0,
+    };
+
+    std::env::set_var("CARGO_SUBCOMMAND_TEST_APPLY_INHERITED", "process");
+    let mut cmd = Command::new("cargo");
+    cmd.env("CARGO_SUBCOMMAND_TEST_APPLY_CMD", "cmd");
+    cmd.env("CARGO_SUBCOMMAND_TEST_APPLY_FORCED", "cmd");
+    config.apply_env_vars(&mut cmd).unwrap();
+
+    let envs = cmd
+        .get_envs()
+        .map(|(key, value)| (key.to_str().unwrap(), value.and_then(|v| v.to_str())))
+        .collect::<BTreeMap<_, _>>();
+    assert_eq!(
+        envs,
+        BTreeMap::from([
+            ("CARGO_SUBCOMMAND_TEST_APPLY_CMD", Some("cmd")),
+            ("CARGO_SUBCOMMAND_TEST_APPLY_FORCED", Some("forced")),
+            ("CARGO_SUBCOMMAND_TEST_APPLY_NEW", Some("new")),
+        ])
+    );
+    // The process environment is left untouched
+    assert!(std::env::var_os("CARGO_SUBCOMMAND_TEST_APPLY_NEW").is_none());
+}
+
+#[test]
+fn test_build_target() {
+    let parse = |config| {
+        MergedConfig {
+            configs: vec![LocalizedConfig {
+                config: toml::from_str::<Config>(config).unwrap(),
+                workspace: PathBuf::from("/parent"),
+                definition: Definition::Path(PathBuf::from("/parent/.cargo/config.toml")),
+            }],
+            cli_args: 0,
+        }
+        .build_target()
+        .unwrap()
+        .into_iter()
+        .map(|target| target.val)
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        parse("build.target = \"aarch64-linux-android\""),
+        ["aarch64-linux-android"]
+    );
+    // Custom target specifications are relative to the directory containing `.cargo`
+    assert_eq!(
+        parse("build.target = [\"x86_64-unknown-linux-gnu\", \"specs/custom.json\"]"),
+        ["x86_64-unknown-linux-gnu", "/parent/specs/custom.json"]
+    );
+
+    let file = |dir: &str, config| LocalizedConfig {
+        config: toml::from_str::<Config>(config).unwrap(),
+        workspace: PathBuf::from(dir),
+        definition: Definition::Path(Path::new(dir).join(".cargo/config.toml")),
+    };
+    let merge = |parent, child| {
+        MergedConfig {
+            configs: vec![file("/parent/child", child), file("/parent", parent)],
+            cli_args: 0,
+        }
+        .build_target()
+    };
+    let parent = Definition::Path(PathBuf::from("/parent/.cargo/config.toml"));
+    let child = Definition::Path(PathBuf::from("/parent/child/.cargo/config.toml"));
+
+    // Arrays from multiple config files are concatenated, lowest precedence first
+    assert_eq!(
+        merge(
+            "build.target = [\"aarch64-linux-android\"]",
+            "build.target = [\"wasm32-wasip1\"]"
+        )
+        .unwrap(),
+        [
+            Value {
+                val: "aarch64-linux-android".to_owned(),
+                definition: parent.clone(),
+            },
+            Value {
+                val: "wasm32-wasip1".to_owned(),
+                definition: child.clone(),
+            },
+        ]
+    );
+    // Whereas a string in a deeper directory overrides the parent
+    assert_eq!(
+        merge(
+            "build.target = \"x86_64-unknown-linux-gnu\"",
+            "build.target = \"aarch64-linux-android\""
+        )
+        .unwrap(),
+        [Value {
+            val: "aarch64-linux-android".to_owned(),
+            definition: child,
+        }]
+    );
+    // And strings cannot be merged with arrays
+    assert!(matches!(
+        merge(
+            "build.target = \"aarch64-linux-android\"",
+            "build.target = [\"wasm32-wasip1\"]"
+        ),
+        Err(Error::ConfigMerge { key, lower, .. }) if key == "build.target" && *lower == parent
+    ));
+}
+
+#[test]
+fn test_target_config() {
+    let parent = LocalizedConfig {
+        config: toml::from_str::<Config>(
+            r#"
+[target.aarch64-linux-android]
+linker = "ndk/bin/clang"
+rustflags = "-C link-arg=-landroid"
+
+[target.'cfg(target_os = "android")']
+runner = "adb-run --device emulator"
+rustflags = ["-C", "opt-level=s"]
+
+[target.'cfg(windows)']
+runner = "wine"
+"#,
+        )
+        .unwrap(),
+        workspace: PathBuf::from("/parent"),
+        definition: Definition::Path(PathBuf::from("/parent/.cargo/config.toml")),
+    };
+    let child = LocalizedConfig {
+        config: toml::from_str::<Config>(
+            r#"
+[target.'cfg(unix)']
+linker = "cc"
+"#,
+        )
+        .unwrap(),
+        workspace: PathBuf::from("/parent/child"),
+        definition: Definition::Path(PathBuf::from("/parent/child/.cargo/config.toml")),
+    };
+    let config = MergedConfig {
+        configs: vec![child, parent],
+        cli_args: 0,
+    };
+    let cfgs = ["unix", "target_os=\"android\""].map(|cfg| cfg.parse::<Cfg>().unwrap());
+
+    let target = config.target("aarch64-linux-android", &cfgs).unwrap();
+    // Linkers with a path separator are relative to the directory containing `.cargo`,
+    // and take precedence over the linker from `cfg(unix)`
+    assert_eq!(
+        target.linker.unwrap().val,
+        PathBuf::from("/parent/ndk/bin/clang")
+    );
+    assert_eq!(
+        target.runner.unwrap().val,
+        PathAndArgs {
+            path: PathBuf::from("adb-run"),
+            args: vec!["--device".to_owned(), "emulator".to_owned()],
+        }
+    );
+    assert_eq!(
+        target
+            .rustflags
+            .into_iter()
+            .flat_map(|flags| flags.val)
+            .collect::<Vec<_>>(),
+        ["-C", "link-arg=-landroid", "-C", "opt-level=s"]
+    );
+
+    let target = config.target("armv7-linux-androideabi", &cfgs).unwrap();
+    assert_eq!(target.linker.unwrap().val, PathBuf::from("cc"));
+
+    // Multiple `cfg()` tables providing a runner are ambiguous
+    let cfgs = ["windows", "target_os=\"android\""].map(|cfg| cfg.parse::<Cfg>().unwrap());
+    assert!(matches!(
+        config.target("x86_64-pc-windows-gnu", &cfgs),
+        Err(Error::AmbiguousCfgTarget {
+            field: "runner",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_rustflags_merging() {
+    let parent = LocalizedConfig {
+        config: toml::from_str::<Config>(
+            r#"
+build.rustflags = ["-Ctparent"]
+build.rustdocflags = "-Cparent"
+target.aarch64-linux-android.rustflags = ["-Ctparent"]
+target.x86_64-unknown-linux-gnu.rustflags = "-Cparent"
+target.'cfg(unix)'.rustflags = "-Cparent-unix"
+"#,
+        )
+        .unwrap(),
+        workspace: PathBuf::from("/parent"),
+        definition: Definition::Path(PathBuf::from("/parent/.cargo/config.toml")),
+    };
+    let child = LocalizedConfig {
+        config: toml::from_str::<Config>(
+            r#"
+build.rustflags = ["-Ctchild"]
+build.rustdocflags = "-Cchild"
+target.aarch64-linux-android.rustflags = ["-Ctchild"]
+target.x86_64-unknown-linux-gnu.rustflags = ["-Cchild"]
+target.'cfg(unix)'.rustflags = "-Cchild-unix"
+"#,
+        )
+        .unwrap(),
+        workspace: PathBuf::from("/parent/child"),
+        definition: Definition::Path(PathBuf::from("/parent/child/.cargo/config.toml")),
+    };
+    let config = MergedConfig {
+        configs: vec![child, parent],
+        cli_args: 0,
+    };
+    let parent = Definition::Path(PathBuf::from("/parent/.cargo/config.toml"));
+    let child = Definition::Path(PathBuf::from("/parent/child/.cargo/config.toml"));
+
+    // Arrays are concatenated across config files, starting with the lowest precedence
+    assert_eq!(
+        config.build_rustflags().unwrap(),
+        [
+            Value {
+                val: vec!["-Ctparent".to_owned()],
+                definition: parent.clone(),
+            },
+            Value {
+                val: vec!["-Ctchild".to_owned()],
+                definition: child.clone(),
+            },
+        ]
+    );
+    // Whereas strings override those with a lower precedence
+    assert_eq!(
+        config.build_rustdocflags().unwrap(),
+        [Value {
+            val: vec!["-Cchild".to_owned()],
+            definition: child.clone(),
+        }]
+    );
+
+    let cfgs = ["unix".parse::<Cfg>().unwrap()];
+    let rustflags = config
+        .target("aarch64-linux-android", &cfgs)
+        .unwrap()
+        .rustflags;
+    assert_eq!(
+        rustflags
+            .iter()
+            .flat_map(|flags| flags.val.iter().map(String::as_str))
+            .collect::<Vec<_>>(),
+        ["-Ctparent", "-Ctchild", "-Cchild-unix"]
+    );
+    assert_eq!(
+        rustflags
+            .into_iter()
+            .map(|flags| flags.definition)
+            .collect::<Vec<_>>(),
+        [parent, child.clone(), child]
+    );
+
+    // Strings and arrays cannot be merged
+    assert!(matches!(
+        config.target("x86_64-unknown-linux-gnu", &cfgs),
+        Err(Error::ConfigMerge { key, .. }) if key == "target.x86_64-unknown-linux-gnu.rustflags"
+    ));
+}
+
+#[test]
+fn test_env_overrides() {
+    assert_eq!(env_key(&["build", "target-dir"]), "CARGO_BUILD_TARGET_DIR");
+    assert_eq!(
+        env_key(&["target", "aarch64-linux-android", "runner"]),
+        "CARGO_TARGET_AARCH64_LINUX_ANDROID_RUNNER"
+    );
+    assert_eq!(
+        env_key(&["profile", "release", "lto"]),
+        "CARGO_PROFILE_RELEASE_LTO"
+    );
+
+    std::env::set_var("CARGO_SUBCOMMAND_TEST_BOOL", "true");
+    std::env::set_var("CARGO_SUBCOMMAND_TEST_INT", "12");
+    std::env::set_var("CARGO_SUBCOMMAND_TEST_LIST", "-C  opt-level=3");
+
+    let key = |name| ["subcommand", "test", name];
+    assert_eq!(env_var::<bool>(&key("bool")).unwrap(), Some(true));
+    assert_eq!(env_var::<u32>(&key("int")).unwrap(), Some(12));
+    assert_eq!(
+        env_var::<String>(&key("int")).unwrap(),
+        Some("12".to_owned())
+    );
+    assert_eq!(
+        env_var::<Vec<String>>(&key("list")).unwrap(),
+        Some(vec!["-C".to_owned(), "opt-level=3".to_owned()])
+    );
+    assert_eq!(env_var::<String>(&key("unset")).unwrap(), None);
+    assert!(matches!(
+        env_var::<u32>(&key("list")),
+        Err(Error::ConfigEnvVar(..))
+    ));
+
+    // Environment variables and `--config` strings replace lists from config files
+    let file = LocalizedConfig {
+        config: toml::from_str::<Config>(
+            r#"
+build.target = "aarch64-linux-android"
+target.cargo-subcommand-test.rustflags = ["-Cfile"]
+"#,
+        )
+        .unwrap(),
+        workspace: PathBuf::from("/parent"),
+        definition: Definition::Path(PathBuf::from("/parent/.cargo/config.toml")),
+    };
+    let config = MergedConfig {
+        configs: vec![file],
+        cli_args: 0,
+    }
+    .with_cli_args(
+        &["build.target = \"x86_64-unknown-linux-gnu\"".to_owned()],
+        Path::new("/cwd"),
+    )
+    .unwrap();
+    assert_eq!(
+        config.build_target().unwrap(),
+        [Value {
+            val: "x86_64-unknown-linux-gnu".to_owned(),
+            definition: Definition::Cli(None),
+        }]
+    );
+
+    std::env::set_var(
+        "CARGO_TARGET_CARGO_SUBCOMMAND_TEST_RUSTFLAGS",
+        "-Cenv -Cvar",
+    );
+    assert_eq!(
+        config
+            .target("cargo-subcommand-test", &[])
+            .unwrap()
+            .rustflags,
+        [Value {
+            val: vec!["-Cenv".to_owned(), "-Cvar".to_owned()],
+            definition: Definition::Environment(
+                "CARGO_TARGET_CARGO_SUBCOMMAND_TEST_RUSTFLAGS".to_owned()
+            ),
+        }]
+    );
+}
+
+#[test]
+fn test_get() {
+    let file = LocalizedConfig {
+        config: toml::from_str::<Config>(
+            r#"
+build.jobs = 4
+profile.release.lto = "fat"
+profile.release.debug = true
+"#,
+        )
+        .unwrap(),
+        workspace: PathBuf::from("/parent"),
+        definition: Definition::Path(PathBuf::from("/parent/.cargo/config.toml")),
+    };
+    let config = MergedConfig {
+        configs: vec![file],
+        cli_args: 0,
+    }
+    .with_cli_args(
+        &["profile.release.debug = false".to_owned()],
+        Path::new("/cwd"),
+    )
+    .unwrap();
+
+    assert_eq!(
+        config.get::<u32>(&["build", "jobs"]).unwrap(),
+        Some(Value {
+            val: 4,
+            definition: Definition::Path(PathBuf::from("/parent/.cargo/config.toml")),
+        })
+    );
+    // `--config` arguments take precedence over config files
+    assert_eq!(
+        config
+            .get::<bool>(&["profile", "release", "debug"])
+            .unwrap(),
+        Some(Value {
+            val: false,
+            definition: Definition::Cli(None),
+        })
+    );
+    assert_eq!(
+        config.get::<String>(&["profile", "dev", "lto"]).unwrap(),
+        None
+    );
+    assert!(matches!(
+        config.get::<u32>(&["profile", "release", "lto"]),
+        Err(Error::ConfigValue { key, .. }) if key == "profile.release.lto"
+    ));
+
+    std::env::set_var("CARGO_SUBCOMMAND_TEST_GET", "thin");
+    assert_eq!(
+        config
+            .get::<String>(&["subcommand", "test", "get"])
+            .unwrap(),
+        Some(Value {
+            val: "thin".to_owned(),
+            definition: Definition::Environment("CARGO_SUBCOMMAND_TEST_GET".to_owned()),
+        })
+    );
+}
+
+#[test]
+fn test_definition() {
+    let cwd = Path::new("/cwd");
+    let file = Definition::Path(PathBuf::from("/parent/.cargo/config.toml"));
+    assert_eq!(file.root(cwd), Path::new("/parent"));
+    assert_eq!(file.to_string(), "`/parent/.cargo/config.toml`");
+
+    let env = Definition::Environment("CARGO_BUILD_TARGET_DIR".to_owned());
+    assert_eq!(env.root(cwd), cwd);
+    assert_eq!(
+        env.to_string(),
+        "environment variable `CARGO_BUILD_TARGET_DIR`"
+    );
+
+    assert_eq!(Definition::Cli(None).root(cwd), cwd);
+    assert_eq!(Definition::Cli(None).to_string(), "--config cli option");
+
+    let flag = Definition::Flag("--target-dir");
+    assert_eq!(flag.root(cwd), cwd);
+    assert_eq!(flag.to_string(), "`--target-dir` flag");
+}
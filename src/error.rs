@@ -1,3 +1,6 @@
+use crate::artifact::ArtifactType;
+use crate::config::{Definition, EnvError, Value};
+use crate::manifest::CrateType;
 use glob::{GlobError, PatternError};
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::io::Error as IoError;
@@ -10,22 +13,80 @@ pub enum Error {
     ManifestNotAWorkspace,
     ManifestNotFound,
     RustcNotFound,
+    PrintCfg(String),
+    InvalidCfg(String, &'static str),
+    AmbiguousCfgTarget {
+        field: &'static str,
+        first: Box<Value<String>>,
+        second: Box<Value<String>>,
+    },
+    ConfigMerge {
+        key: String,
+        lower: Box<Definition>,
+        higher: Box<Definition>,
+    },
     ManifestPathNotFound,
     GlobPatternError(&'static str),
     Glob(GlobError),
     UnexpectedWorkspace(PathBuf),
     NoPackageInManifest(PathBuf),
     PackageNotFound(PathBuf, String),
+    ExcludeWithoutWorkspace,
+    ExcludedPackageNotFound(PathBuf, String),
     ManifestNotInWorkspace {
         manifest: PathBuf,
         workspace_manifest: PathBuf,
     },
+    PackageWorkspaceNotAWorkspace {
+        manifest: PathBuf,
+        workspace_manifest: PathBuf,
+    },
+    DefaultMemberNotInWorkspace {
+        member: PathBuf,
+        workspace_manifest: PathBuf,
+    },
+    InheritedFieldNotFound {
+        manifest: PathBuf,
+        field: &'static str,
+    },
+    InheritWorkspaceFalse {
+        manifest: PathBuf,
+        field: &'static str,
+    },
+    NonUtf8Path(PathBuf),
+    Env(EnvError),
+    IncompatibleCrateType {
+        name: String,
+        artifact: ArtifactType,
+        crate_type: CrateType,
+    },
+    UndeclaredCrateType {
+        name: String,
+        artifact: ArtifactType,
+        crate_type: CrateType,
+        declared: Vec<CrateType>,
+    },
+    UnupliftedArtifact {
+        name: String,
+        artifact: ArtifactType,
+    },
     Io(PathBuf, IoError),
     Toml(PathBuf, TomlError),
+    ConfigArg(String, TomlError),
+    ConfigEnvVar(String, TomlError),
+    ConfigValue {
+        key: String,
+        definition: Box<Definition>,
+        error: TomlError,
+    },
     BinNotFound(String),
     ExampleNotFound(String),
     DuplicateBin(String),
     DuplicateExample(String),
+    TestNotFound(String),
+    BenchNotFound(String),
+    DuplicateTest(String),
+    DuplicateBench(String),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -40,6 +101,31 @@ impl Display for Error {
             Self::ManifestNotFound => "Didn't find Cargo.toml.",
             Self::ManifestPathNotFound => "The manifest-path must be a path to a Cargo.toml file",
             Self::RustcNotFound => "Didn't find rustc.",
+            Self::PrintCfg(stderr) => {
+                return write!(f, "failed to run `rustc --print cfg`: {stderr}")
+            }
+            Self::InvalidCfg(expr, reason) => {
+                return write!(f, "failed to parse `{expr}` as a cfg expression: {reason}")
+            }
+            Self::AmbiguousCfgTarget {
+                field,
+                first,
+                second,
+            } => {
+                return write!(
+                    f,
+                    "several matching instances of `target.'cfg(..)'.{field}` in configurations
+first match `{}` located in {}
+second match `{}` located in {}",
+                    first.val, first.definition, second.val, second.definition
+                )
+            }
+            Self::ConfigMerge { key, lower, higher } => {
+                return write!(
+                    f,
+                    "failed to merge `{key}` from {higher} into {lower}: cannot mix a string and an array"
+                )
+            }
             Self::GlobPatternError(error) => error,
             Self::Glob(error) => return error.fmt(f),
             Self::UnexpectedWorkspace(path) => {
@@ -60,6 +146,17 @@ impl Display for Error {
                     workspace.display()
                 )
             }
+            Self::ExcludeWithoutWorkspace => {
+                "`--exclude` can only be used together with `--workspace`"
+            }
+            Self::ExcludedPackageNotFound(workspace, pattern) => {
+                return write!(
+                    f,
+                    "excluded package(s) `{}` not found in workspace `{}`",
+                    pattern,
+                    workspace.display()
+                )
+            }
             Self::ManifestNotInWorkspace {
                 manifest,
                 workspace_manifest,
@@ -69,19 +166,101 @@ current:   {}
 workspace: {workspace_manifest_path}
 
 this may be fixable by adding `{package_subpath}` to the `workspace.members` array of the manifest located at: {workspace_manifest_path}
-Alternatively, to keep it out of the workspace, add an empty `[workspace]` table to the package's manifest.",
-                    // TODO: Parse workspace.exclude and add back "add the package to the `workspace.exclude` array, or"
+Alternatively, to keep it out of the workspace, add the package to the `workspace.exclude` array, or add an empty `[workspace]` table to the package's manifest.",
                     manifest.display(),
-                    package_subpath = manifest.parent().unwrap().strip_prefix(workspace_manifest.parent().unwrap()).unwrap().display(),
+                    // Packages that point to their root with `package.workspace` may live outside of it
+                    package_subpath = manifest.parent().unwrap().strip_prefix(workspace_manifest.parent().unwrap()).unwrap_or(manifest.parent().unwrap()).display(),
                     workspace_manifest_path = workspace_manifest.display(),
                 )
             },
+            Self::PackageWorkspaceNotAWorkspace {
+                manifest,
+                workspace_manifest,
+            } => {
+                return write!(
+                    f,
+                    "package `{}` points to workspace root `{}` with `package.workspace`, but that manifest does not contain a `[workspace]`",
+                    manifest.display(),
+                    workspace_manifest.display()
+                )
+            }
+            Self::DefaultMemberNotInWorkspace {
+                member,
+                workspace_manifest,
+            } => {
+                return write!(
+                    f,
+                    "package `{}` is listed in workspace's default-members but is not a member of workspace `{}`",
+                    member.display(),
+                    workspace_manifest.display()
+                )
+            }
+            Self::InheritedFieldNotFound { manifest, field } => {
+                return write!(
+                    f,
+                    "{}: error inheriting `{field}` from workspace root manifest's `workspace.package.{field}`: it is not defined",
+                    manifest.display()
+                )
+            }
+            Self::InheritWorkspaceFalse { manifest, field } => {
+                return write!(
+                    f,
+                    "{}: `{field}.workspace` cannot be false",
+                    manifest.display()
+                )
+            }
+            Self::NonUtf8Path(path) => {
+                return write!(f, "`{}` is not valid UTF-8", path.display())
+            }
+            Self::Env(error) => return error.fmt(f),
+            Self::IncompatibleCrateType {
+                name,
+                artifact,
+                crate_type,
+            } => {
+                return write!(
+                    f,
+                    "{artifact:?} artifact `{name}` cannot be built as crate type {crate_type:?}"
+                )
+            }
+            Self::UndeclaredCrateType {
+                name,
+                artifact,
+                crate_type,
+                declared,
+            } => {
+                return write!(
+                    f,
+                    "{artifact:?} artifact `{name}` is not built as crate type {crate_type:?}, only as {declared:?}; add it to `crate-type` in the manifest"
+                )
+            }
             Self::Io(path, error) => return write!(f, "{}: {}", path.display(), error),
             Self::Toml(file, error) => return write!(f, "{}: {}", file.display(), error),
+            Self::ConfigEnvVar(var, error) => {
+                return write!(f, "failed to parse environment variable `{var}`: {error}")
+            }
+            Self::UnupliftedArtifact { name, artifact } => {
+                return write!(
+                    f,
+                    "{artifact:?} artifact `{name}` is only built in `deps` under a hashed file name, so its path is unknown"
+                )
+            }
+            Self::ConfigArg(arg, error) => {
+                return write!(f, "failed to parse `--config {arg}`: {error}")
+            }
+            Self::ConfigValue {
+                key,
+                definition,
+                error,
+            } => return write!(f, "failed to parse `{key}` from {definition}: {error}"),
             Self::BinNotFound(name) => return write!(f, "Can't find `{name}` bin at `src/bin/{name}.rs` or `src/bin/{name}/main.rs`. Please specify bin.path if you want to use a non-default path."),
             Self::ExampleNotFound(name) => return write!(f, "Can't find `{name}` example at `examples/{name}.rs` or `examples/{name}/main.rs`. Please specify examples.path if you want to use a non-default path."),
             Self::DuplicateBin(name) => return write!(f, "found duplicate binary name {name}, but all binary targets must have a unique name"),
             Self::DuplicateExample(name) => return write!(f, "found duplicate example name {name}, but all example targets must have a unique name"),
+            Self::TestNotFound(name) => return write!(f, "Can't find `{name}` test at `tests/{name}.rs` or `tests/{name}/main.rs`. Please specify test.path if you want to use a non-default path."),
+            Self::BenchNotFound(name) => return write!(f, "Can't find `{name}` bench at `benches/{name}.rs` or `benches/{name}/main.rs`. Please specify bench.path if you want to use a non-default path."),
+            Self::DuplicateTest(name) => return write!(f, "found duplicate test name {name}, but all test targets must have a unique name"),
+            Self::DuplicateBench(name) => return write!(f, "found duplicate bench name {name}, but all bench targets must have a unique name"),
         })
     }
 }
@@ -94,6 +273,12 @@ impl From<PatternError> for Error {
     }
 }
 
+impl From<EnvError> for Error {
+    fn from(error: EnvError) -> Self {
+        Self::Env(error)
+    }
+}
+
 impl From<GlobError> for Error {
     fn from(error: GlobError) -> Self {
         Self::Glob(error)
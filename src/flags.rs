@@ -0,0 +1,129 @@
+use crate::config::{Definition, Value};
+use crate::error::{Error, Result};
+use std::env::VarError;
+use std::process::Command;
+
+/// Separator between flags in `CARGO_ENCODED_RUSTFLAGS` and `CARGO_ENCODED_RUSTDOCFLAGS`
+const SEPARATOR: char = '\x1f';
+
+/// Flags for `rustc` or `rustdoc`, resolved with [cargo's precedence] by
+/// [`Subcommand::rustflags()`][crate::Subcommand::rustflags()] and
+/// [`Subcommand::rustdocflags()`][crate::Subcommand::rustdocflags()].
+///
+/// [cargo's precedence]: https://doc.rust-lang.org/cargo/reference/config.html#buildrustflags
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Flags {
+    var: &'static str,
+    flags: Vec<String>,
+    definitions: Vec<Definition>,
+}
+
+impl Flags {
+    /// Reads the flags from `CARGO_ENCODED_<var>`, or else from the whitespace-separated `<var>`
+    pub(crate) fn from_env(var: &'static str) -> Result<Option<Self>> {
+        let encoded_var = format!("CARGO_ENCODED_{var}");
+        if let Some(encoded) = env_var(&encoded_var)? {
+            // An empty variable sets no flags at all, rather than a single empty flag
+            let flags = if encoded.is_empty() {
+                vec![]
+            } else {
+                encoded.split(SEPARATOR).map(str::to_owned).collect()
+            };
+            return Ok(Some(Self {
+                var,
+                flags,
+                definitions: vec![Definition::Environment(encoded_var)],
+            }));
+        }
+        Ok(env_var(var)?.map(|flags| Self {
+            var,
+            flags: flags.split_whitespace().map(str::to_owned).collect(),
+            definitions: vec![Definition::Environment(var.to_owned())],
+        }))
+    }
+
+    /// Joins the flags of all config `values`, in order
+    pub(crate) fn from_config(var: &'static str, values: Vec<Value<Vec<String>>>) -> Self {
+        let mut flags = Self {
+            var,
+            flags: vec![],
+            definitions: vec![],
+        };
+        for Value { val, definition } in values {
+            flags.flags.extend(val);
+            flags.definitions.push(definition);
+        }
+        flags
+    }
+
+    pub fn flags(&self) -> &[String] {
+        &self.flags
+    }
+
+    /// Where the flags were defined, which is empty when no flags were configured
+    pub fn definitions(&self) -> &[Definition] {
+        &self.definitions
+    }
+
+    /// Encodes the flags for `CARGO_ENCODED_RUSTFLAGS` or `CARGO_ENCODED_RUSTDOCFLAGS`,
+    /// which (unlike `RUSTFLAGS`) preserves whitespace within flags.
+    pub fn encode(&self) -> String {
+        self.flags.join(&SEPARATOR.to_string())
+    }
+
+    /// Passes the flags to a child `cargo` process via `CARGO_ENCODED_RUSTFLAGS` or
+    /// `CARGO_ENCODED_RUSTDOCFLAGS`, and removes the lower-precedence `RUSTFLAGS` or
+    /// `RUSTDOCFLAGS` it would otherwise inherit.
+    pub fn apply(&self, cmd: &mut Command) {
+        cmd.env(format!("CARGO_ENCODED_{}", self.var), self.encode());
+        cmd.env_remove(self.var);
+    }
+}
+
+fn env_var(key: &str) -> Result<Option<String>> {
+    match std::env::var(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(VarError::NotPresent) => Ok(None),
+        Err(e) => Err(Error::Env(e.into())),
+    }
+}
+
+#[test]
+fn test_flags_from_env() {
+    std::env::set_var("CARGO_SUBCOMMAND_TEST_FLAGS", "-C  opt-level=3");
+    let flags = Flags::from_env("CARGO_SUBCOMMAND_TEST_FLAGS")
+        .unwrap()
+        .unwrap();
+    assert_eq!(flags.flags(), ["-C", "opt-level=3"]);
+    assert_eq!(flags.encode(), "-C\x1fopt-level=3");
+
+    // The encoded variable takes precedence, and may contain whitespace
+    std::env::set_var(
+        "CARGO_ENCODED_CARGO_SUBCOMMAND_TEST_FLAGS",
+        "-Clink-arg=-L/path with spaces\x1f--cfg=foo",
+    );
+    let flags = Flags::from_env("CARGO_SUBCOMMAND_TEST_FLAGS")
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        flags.flags(),
+        ["-Clink-arg=-L/path with spaces", "--cfg=foo"]
+    );
+    assert_eq!(
+        flags.definitions(),
+        [Definition::Environment(
+            "CARGO_ENCODED_CARGO_SUBCOMMAND_TEST_FLAGS".to_owned()
+        )]
+    );
+
+    std::env::set_var("CARGO_ENCODED_CARGO_SUBCOMMAND_TEST_FLAGS", "");
+    let flags = Flags::from_env("CARGO_SUBCOMMAND_TEST_FLAGS")
+        .unwrap()
+        .unwrap();
+    assert!(flags.flags().is_empty());
+
+    assert_eq!(
+        Flags::from_env("CARGO_SUBCOMMAND_TEST_UNSET").unwrap(),
+        None
+    );
+}
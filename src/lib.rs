@@ -1,16 +1,27 @@
 mod args;
 mod artifact;
+mod cfg;
 mod config;
 mod error;
+mod flags;
 mod manifest;
+mod package;
 mod profile;
 mod subcommand;
+mod triple;
 mod utils;
 
 pub use args::Args;
-pub use artifact::{Artifact, ArtifactType};
-pub use config::{EnvError, EnvOption, LocalizedConfig};
+pub use artifact::{Artifact, ArtifactFile, ArtifactType, FileFlavor};
+pub use cfg::{rustc_cfgs, Cfg, CfgExpr};
+pub use config::{
+    cargo_home, env_key, Definition, EnvError, EnvOption, LocalizedConfig, MergedConfig,
+    PathAndArgs, ResolvedTarget, Value,
+};
 pub use error::Error;
+pub use flags::Flags;
 pub use manifest::CrateType;
+pub use package::Package;
 pub use profile::Profile;
 pub use subcommand::Subcommand;
+pub use triple::Triple;
@@ -1,6 +1,6 @@
 use serde::Deserialize;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     path::{Path, PathBuf},
 };
 
@@ -16,6 +16,18 @@ pub struct Manifest {
     pub bins: Vec<Bin>,
     #[serde(default, rename = "example")]
     pub examples: Vec<Example>,
+    #[serde(default, rename = "test")]
+    pub tests: Vec<Test>,
+    #[serde(default, rename = "bench")]
+    pub benches: Vec<Bench>,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+    #[serde(default, rename = "dev-dependencies")]
+    pub dev_dependencies: BTreeMap<String, Dependency>,
+    #[serde(default, rename = "build-dependencies")]
+    pub build_dependencies: BTreeMap<String, Dependency>,
+    #[serde(default)]
+    pub target: BTreeMap<String, Target>,
 }
 
 impl Manifest {
@@ -35,56 +47,144 @@ impl Manifest {
         // Check all member packages inside the workspace
         let mut all_members = HashMap::new();
 
-        for member in &workspace.members {
-            // XXX: Cargo would fail if the glob yielded no file results.  But it allows cases where
-            // the only results are non-directories, including non-glob *file* paths in `members`...
-            for manifest_dir in glob::glob(workspace_root.join(member).to_str().unwrap())? {
-                let manifest_dir = manifest_dir?;
-                if !dunce::canonicalize(&manifest_dir)
-                    .map_err(|e| Error::Io(manifest_dir.clone(), e))?
-                    .is_dir()
-                {
-                    continue;
-                }
-                let manifest_path = manifest_dir.join("Cargo.toml");
-                let manifest = Manifest::parse_from_toml(&manifest_path)?;
-
-                // Workspace members cannot themselves be/contain a new workspace
-                if manifest.workspace.is_some() {
-                    return Err(Error::UnexpectedWorkspace(manifest_path));
-                }
-
-                // And because they cannot contain a [workspace], they may not be a virtual manifest
-                // and must hence contain [package]
-                if manifest.package.is_none() {
-                    return Err(Error::NoPackageInManifest(manifest_path));
-                }
-
-                all_members.insert(manifest_dir, (manifest_path, manifest));
+        for manifest_dir in expand_globs(&workspace_root, &workspace.members)? {
+            // Glob results that are listed in `workspace.exclude` are not members
+            if workspace.is_excluded(&workspace_root, &manifest_dir) {
+                continue;
+            }
+
+            let manifest_path = manifest_dir.join("Cargo.toml");
+            let manifest = Manifest::parse_from_toml(&manifest_path)?;
+
+            // Workspace members cannot themselves be/contain a new workspace
+            if manifest.workspace.is_some() {
+                return Err(Error::UnexpectedWorkspace(manifest_path));
+            }
+
+            // And because they cannot contain a [workspace], they may not be a virtual manifest
+            // and must hence contain [package]
+            if manifest.package.is_none() {
+                return Err(Error::NoPackageInManifest(manifest_path));
+            }
+
+            all_members.insert(manifest_dir, (manifest_path, manifest));
+        }
+
+        // Path dependencies of the root package and all members that reside within the
+        // workspace root are implicitly members too, including their own path dependencies
+        let mut pending = all_members
+            .iter()
+            .map(|(manifest_dir, (_, manifest))| {
+                manifest.path_dependencies(manifest_dir, &workspace_root, workspace)
+            })
+            .chain(std::iter::once(self.path_dependencies(
+                &workspace_root,
+                &workspace_root,
+                workspace,
+            )))
+            .flatten()
+            .collect::<Vec<_>>();
+
+        while let Some(dependency_dir) = pending.pop() {
+            let manifest_dir = utils::canonicalize(&dependency_dir)?;
+            if manifest_dir == workspace_root
+                || !manifest_dir.starts_with(&workspace_root)
+                || workspace.is_excluded(&workspace_root, &manifest_dir)
+                || all_members.contains_key(&manifest_dir)
+            {
+                continue;
+            }
+
+            let manifest_path = manifest_dir.join("Cargo.toml");
+            let manifest = Manifest::parse_from_toml(&manifest_path)?;
+
+            if manifest.workspace.is_some() {
+                return Err(Error::UnexpectedWorkspace(manifest_path));
+            }
+
+            if manifest.package.is_none() {
+                return Err(Error::NoPackageInManifest(manifest_path));
             }
+
+            pending.extend(manifest.path_dependencies(&manifest_dir, &workspace_root, workspace));
+            all_members.insert(manifest_dir, (manifest_path, manifest));
         }
 
         Ok(all_members)
     }
 
+    /// Returns the directories of all [path dependencies] of this package, relative to the current
+    /// working directory.  Dependencies inherited from `[workspace.dependencies]` are resolved
+    /// relative to the workspace root, as that is where their path is specified.
+    ///
+    /// [path dependencies]: https://doc.rust-lang.org/cargo/reference/specifying-dependencies.html#specifying-path-dependencies
+    fn path_dependencies(
+        &self,
+        manifest_dir: &Path,
+        workspace_root: &Path,
+        workspace: &Workspace,
+    ) -> Vec<PathBuf> {
+        self.dependencies
+            .iter()
+            .chain(&self.dev_dependencies)
+            .chain(&self.build_dependencies)
+            .chain(self.target.values().flat_map(|target| {
+                target
+                    .dependencies
+                    .iter()
+                    .chain(&target.dev_dependencies)
+                    .chain(&target.build_dependencies)
+            }))
+            .filter_map(|(name, dependency)| match dependency {
+                Dependency::Detailed {
+                    path: Some(path), ..
+                } => Some(manifest_dir.join(path)),
+                Dependency::Detailed {
+                    workspace: true, ..
+                } => match workspace.dependencies.get(name) {
+                    Some(Dependency::Detailed {
+                        path: Some(path), ..
+                    }) => Some(workspace_root.join(path)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the directories of all packages listed in [`workspace.default-members`],
+    /// or [`None`] if the key is not set.
+    ///
+    /// [`workspace.default-members`]: https://doc.rust-lang.org/cargo/reference/workspaces.html#the-default-members-field
+    pub fn default_members(&self, workspace_root: &Path) -> Result<Option<Vec<PathBuf>>> {
+        let workspace = self
+            .workspace
+            .as_ref()
+            .ok_or(Error::ManifestNotAWorkspace)?;
+        let workspace_root = utils::canonicalize(workspace_root)?;
+
+        workspace
+            .default_members
+            .as_ref()
+            .map(|default_members| expand_globs(&workspace_root, default_members))
+            .transpose()
+    }
+
     /// Returns `self` if it contains `[package]` but not `[workspace]`, (i.e. it cannot be
-    /// a workspace nor a virtual manifest), and describes a package named `name` if not [`None`].
+    /// a workspace nor a virtual manifest), and describes a package named by every entry
+    /// in `names`.
     pub fn map_nonvirtual_package(
         self,
         manifest_path: PathBuf,
-        name: Option<&str>,
+        names: &[String],
     ) -> Result<(PathBuf, Self)> {
         if self.workspace.is_some() {
             return Err(Error::UnexpectedWorkspace(manifest_path));
         }
 
         if let Some(package) = &self.package {
-            if let Some(name) = name {
-                if package.name == name {
-                    Ok((manifest_path, self))
-                } else {
-                    Err(Error::PackageNotFound(manifest_path, name.into()))
-                }
+            if let Some(name) = names.iter().find(|&name| name != &package.name) {
+                Err(Error::PackageNotFound(manifest_path, name.clone()))
             } else {
                 Ok((manifest_path, self))
             }
@@ -94,13 +194,67 @@ impl Manifest {
     }
 }
 
+/// Expands the member `patterns` relative to `workspace_root` into a list of canonicalized
+/// directories, skipping any results that are not a directory.
+fn expand_globs(workspace_root: &Path, patterns: &[String]) -> Result<Vec<PathBuf>> {
+    let mut dirs = vec![];
+
+    // Only the member patterns are globs, the workspace root itself should match literally
+    let workspace_root = workspace_root
+        .to_str()
+        .ok_or_else(|| Error::NonUtf8Path(workspace_root.to_owned()))?;
+    let workspace_root = glob::Pattern::escape(workspace_root);
+
+    for pattern in patterns {
+        // XXX: Cargo would fail if the glob yielded no file results.  But it allows cases where
+        // the only results are non-directories, including non-glob *file* paths in `members`...
+        let pattern = Path::new(&workspace_root).join(pattern);
+        let pattern = pattern
+            .to_str()
+            .ok_or_else(|| Error::NonUtf8Path(pattern.clone()))?;
+        for dir in glob::glob(pattern)? {
+            let dir = dir?;
+            let dir = dunce::canonicalize(&dir).map_err(|e| Error::Io(dir, e))?;
+            if dir.is_dir() {
+                dirs.push(dir);
+            }
+        }
+    }
+
+    Ok(dirs)
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct Workspace {
-    // #[serde(default)]
-    // pub default_members: Vec<String>,
+    pub default_members: Option<Vec<String>>,
     #[serde(default)]
     pub members: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+    pub package: Option<WorkspacePackage>,
+    /// <https://doc.rust-lang.org/cargo/reference/workspaces.html#the-metadata-table>
+    pub metadata: Option<toml::Value>,
+}
+
+impl Workspace {
+    /// Returns [`true`] when `manifest_dir` lives inside a path listed in [`workspace.exclude`],
+    /// unless it is also explicitly (i.e. without globs) listed in `workspace.members`.
+    ///
+    /// [`workspace.exclude`]: https://doc.rust-lang.org/cargo/reference/workspaces.html#the-members-and-exclude-fields
+    pub fn is_excluded(&self, workspace_root: &Path, manifest_dir: &Path) -> bool {
+        let excluded = self
+            .exclude
+            .iter()
+            .any(|ex| manifest_dir.starts_with(workspace_root.join(ex)));
+        let explicit_member = self
+            .members
+            .iter()
+            .any(|member| manifest_dir.starts_with(workspace_root.join(member)));
+        excluded && !explicit_member
+    }
 }
 
 const fn default_true() -> bool {
@@ -108,33 +262,206 @@ const fn default_true() -> bool {
 }
 
 #[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub struct Package {
     pub name: String,
+    pub version: Option<Inheritable<String>>,
+    pub authors: Option<Inheritable<Vec<String>>>,
+    pub edition: Option<Inheritable<String>>,
+    pub rust_version: Option<Inheritable<String>>,
+    pub description: Option<Inheritable<String>>,
+    pub documentation: Option<Inheritable<String>>,
+    pub homepage: Option<Inheritable<String>>,
+    pub repository: Option<Inheritable<String>>,
+    pub license: Option<Inheritable<String>>,
+    pub license_file: Option<Inheritable<PathBuf>>,
+    pub keywords: Option<Inheritable<Vec<String>>>,
+    pub categories: Option<Inheritable<Vec<String>>>,
+    /// <https://doc.rust-lang.org/cargo/reference/manifest.html#the-metadata-table>
+    pub metadata: Option<toml::Value>,
+    /// Path to the workspace root, if it is not found by searching the parent directories.
+    ///
+    /// <https://doc.rust-lang.org/cargo/reference/manifest.html#the-workspace-field>
+    pub workspace: Option<PathBuf>,
 
     // https://doc.rust-lang.org/cargo/reference/cargo-targets.html#target-auto-discovery
     #[serde(default = "default_true")]
     pub autobins: bool,
     #[serde(default = "default_true")]
     pub autoexamples: bool,
-    // #[serde(default = "default_true")]
-    // pub autotests: bool,
-    // #[serde(default = "default_true")]
-    // pub autobenches: bool,
+    #[serde(default = "default_true")]
+    pub autotests: bool,
+    #[serde(default = "default_true")]
+    pub autobenches: bool,
+}
+
+impl Package {
+    /// Resolves all fields that may be [inherited] from `[workspace.package]`, as passed in
+    /// `workspace`.  A `license-file` is made absolute against the directory of `manifest_path`,
+    /// or against `workspace_root` when it is inherited.
+    ///
+    /// [inherited]: https://doc.rust-lang.org/cargo/reference/workspaces.html#the-package-table
+    pub fn inherit(
+        &self,
+        manifest_path: &Path,
+        workspace_root: &Path,
+        workspace: &WorkspacePackage,
+    ) -> Result<WorkspacePackage> {
+        fn inherit<T: Clone>(
+            field: &Option<Inheritable<T>>,
+            name: &'static str,
+            manifest_path: &Path,
+            inherited: &Option<T>,
+        ) -> Result<Option<T>> {
+            field
+                .as_ref()
+                .map(|value| value.resolve(name, manifest_path, inherited.as_ref()))
+                .transpose()
+        }
+
+        let license_file = match &self.license_file {
+            Some(Inheritable::Value(path)) => Some(manifest_path.parent().unwrap().join(path)),
+            license_file => inherit(
+                license_file,
+                "license-file",
+                manifest_path,
+                &workspace.license_file,
+            )?
+            .map(|path| workspace_root.join(path)),
+        };
+
+        Ok(WorkspacePackage {
+            version: inherit(&self.version, "version", manifest_path, &workspace.version)?,
+            authors: inherit(&self.authors, "authors", manifest_path, &workspace.authors)?,
+            edition: inherit(&self.edition, "edition", manifest_path, &workspace.edition)?,
+            rust_version: inherit(
+                &self.rust_version,
+                "rust-version",
+                manifest_path,
+                &workspace.rust_version,
+            )?,
+            description: inherit(
+                &self.description,
+                "description",
+                manifest_path,
+                &workspace.description,
+            )?,
+            documentation: inherit(
+                &self.documentation,
+                "documentation",
+                manifest_path,
+                &workspace.documentation,
+            )?,
+            homepage: inherit(
+                &self.homepage,
+                "homepage",
+                manifest_path,
+                &workspace.homepage,
+            )?,
+            repository: inherit(
+                &self.repository,
+                "repository",
+                manifest_path,
+                &workspace.repository,
+            )?,
+            license: inherit(&self.license, "license", manifest_path, &workspace.license)?,
+            license_file,
+            keywords: inherit(
+                &self.keywords,
+                "keywords",
+                manifest_path,
+                &workspace.keywords,
+            )?,
+            categories: inherit(
+                &self.categories,
+                "categories",
+                manifest_path,
+                &workspace.categories,
+            )?,
+        })
+    }
 }
 
+/// Package fields that can be [inherited] by members through `field.workspace = true`
+///
+/// [inherited]: https://doc.rust-lang.org/cargo/reference/workspaces.html#the-package-table
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct WorkspacePackage {
+    pub version: Option<String>,
+    pub authors: Option<Vec<String>>,
+    pub edition: Option<String>,
+    pub rust_version: Option<String>,
+    pub description: Option<String>,
+    pub documentation: Option<String>,
+    pub homepage: Option<String>,
+    pub repository: Option<String>,
+    pub license: Option<String>,
+    pub license_file: Option<PathBuf>,
+    pub keywords: Option<Vec<String>>,
+    pub categories: Option<Vec<String>>,
+}
+
+/// A package field that is either set directly, or inherited from `[workspace.package]`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Inheritable<T> {
+    Value(T),
+    Workspace { workspace: bool },
+}
+
+impl<T: Clone> Inheritable<T> {
+    /// Returns the value of this field, or the `inherited` value from `[workspace.package]` when
+    /// `field.workspace = true` is specified in the package manifest at `manifest_path`.
+    pub fn resolve(
+        &self,
+        field: &'static str,
+        manifest_path: &Path,
+        inherited: Option<&T>,
+    ) -> Result<T> {
+        match self {
+            Self::Value(value) => Ok(value.clone()),
+            Self::Workspace { workspace: true } => {
+                inherited
+                    .cloned()
+                    .ok_or_else(|| Error::InheritedFieldNotFound {
+                        manifest: manifest_path.to_owned(),
+                        field,
+                    })
+            }
+            Self::Workspace { workspace: false } => Err(Error::InheritWorkspaceFalse {
+                manifest: manifest_path.to_owned(),
+                field,
+            }),
+        }
+    }
+}
+
+/// <https://doc.rust-lang.org/cargo/reference/cargo-targets.html#the-crate-type-field>
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum CrateType {
     Bin,
+    /// The compiler-recommended library type, which is currently the same as [`CrateType::Rlib`]
     Lib,
+    Rlib,
+    Dylib,
     Staticlib,
     Cdylib,
+    ProcMacro,
 }
 
 #[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub struct Lib {
     pub name: Option<String>,
     pub path: Option<PathBuf>,
-    // pub crate_type: Vec<CrateType>,
+    pub crate_type: Option<Vec<CrateType>>,
+    /// Shorthand for a `crate-type` of `["proc-macro"]`
+    #[serde(default, alias = "proc_macro")]
+    pub proc_macro: bool,
+    #[serde(default = "default_true")]
+    pub harness: bool,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -145,8 +472,254 @@ pub struct Bin {
 }
 
 #[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub struct Example {
     pub name: String,
     pub path: Option<PathBuf>,
-    // pub crate_type: Vec<CrateType>,
+    pub crate_type: Option<Vec<CrateType>>,
+}
+
+/// <https://doc.rust-lang.org/cargo/reference/cargo-targets.html#integration-tests>
+#[derive(Clone, Debug, Deserialize)]
+pub struct Test {
+    pub name: String,
+    pub path: Option<PathBuf>,
+    /// <https://doc.rust-lang.org/cargo/reference/cargo-targets.html#the-harness-field>
+    #[serde(default = "default_true")]
+    pub harness: bool,
+}
+
+/// <https://doc.rust-lang.org/cargo/reference/cargo-targets.html#benchmarks>
+#[derive(Clone, Debug, Deserialize)]
+pub struct Bench {
+    pub name: String,
+    pub path: Option<PathBuf>,
+    /// <https://doc.rust-lang.org/cargo/reference/cargo-targets.html#the-harness-field>
+    #[serde(default = "default_true")]
+    pub harness: bool,
+}
+
+/// A dependency specification, only parsed as far as needed to discover [path dependencies].
+///
+/// [path dependencies]: https://doc.rust-lang.org/cargo/reference/specifying-dependencies.html#specifying-path-dependencies
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Dependency {
+    Version(#[allow(dead_code)] String),
+    Detailed {
+        path: Option<PathBuf>,
+        #[serde(default)]
+        workspace: bool,
+    },
+}
+
+/// Platform-specific dependency tables in `[target.'cfg(...)']`
+#[derive(Clone, Debug, Deserialize)]
+pub struct Target {
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+    #[serde(default, rename = "dev-dependencies")]
+    pub dev_dependencies: BTreeMap<String, Dependency>,
+    #[serde(default, rename = "build-dependencies")]
+    pub build_dependencies: BTreeMap<String, Dependency>,
+}
+
+#[test]
+fn test_workspace_inheritance() {
+    let workspace = toml::from_str::<Manifest>(
+        r#"
+[workspace]
+members = ["member"]
+
+[workspace.package]
+version = "1.2.3"
+authors = ["Someone"]
+license-file = "LICENSE"
+"#,
+    )
+    .unwrap()
+    .workspace
+    .unwrap()
+    .package
+    .unwrap();
+
+    let package = toml::from_str::<Manifest>(
+        r#"
+[package]
+name = "member"
+version.workspace = true
+authors = { workspace = true }
+edition = "2021"
+license-file.workspace = true
+"#,
+    )
+    .unwrap()
+    .package
+    .unwrap();
+
+    let fields = package
+        .inherit(
+            Path::new("/root/member/Cargo.toml"),
+            Path::new("/root"),
+            &workspace,
+        )
+        .unwrap();
+    assert_eq!(fields.version.as_deref(), Some("1.2.3"));
+    assert_eq!(fields.authors, Some(vec!["Someone".to_owned()]));
+    assert_eq!(fields.edition.as_deref(), Some("2021"));
+    assert_eq!(fields.description, None);
+    assert_eq!(
+        fields.license_file.as_deref(),
+        Some(Path::new("/root/LICENSE"))
+    );
+
+    let package = toml::from_str::<Manifest>(
+        r#"
+[package]
+name = "member"
+description.workspace = true
+"#,
+    )
+    .unwrap()
+    .package
+    .unwrap();
+
+    assert!(matches!(
+        package.inherit(
+            Path::new("/root/member/Cargo.toml"),
+            Path::new("/root"),
+            &workspace,
+        ),
+        Err(Error::InheritedFieldNotFound {
+            field: "description",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_test_and_bench_targets() {
+    let manifest = toml::from_str::<Manifest>(
+        r#"
+[package]
+name = "foo"
+autobenches = false
+
+[[test]]
+name = "integration"
+
+[[bench]]
+name = "criterion"
+path = "perf/criterion.rs"
+harness = false
+"#,
+    )
+    .unwrap();
+
+    let package = manifest.package.unwrap();
+    assert!(package.autotests);
+    assert!(!package.autobenches);
+    assert!(manifest.tests[0].harness);
+    assert_eq!(manifest.benches[0].name, "criterion");
+    assert!(!manifest.benches[0].harness);
+}
+
+#[test]
+fn test_workspace_exclude() {
+    let dir = crate::utils::create_test_dir(
+        "workspace-exclude",
+        &[
+            (
+                "Cargo.toml",
+                "[workspace]\nmembers = [\"crates/*\", \"vendor/kept\"]\nexclude = [\"crates/excluded\", \"vendor\"]",
+            ),
+            ("crates/a/Cargo.toml", "[package]\nname = \"a\""),
+            ("crates/excluded/Cargo.toml", "[package]\nname = \"excluded\""),
+            ("vendor/kept/Cargo.toml", "[package]\nname = \"kept\""),
+            ("vendor/other/Cargo.toml", "[package]\nname = \"other\""),
+        ],
+    );
+    let (manifest_path, manifest) = utils::find_manifest(&dir).unwrap();
+
+    // Explicitly listed members are kept, even inside an excluded directory
+    let workspace = manifest.workspace.as_ref().unwrap();
+    assert!(workspace.is_excluded(&dir, &dir.join("crates/excluded")));
+    assert!(workspace.is_excluded(&dir, &dir.join("vendor/other")));
+    assert!(!workspace.is_excluded(&dir, &dir.join("vendor/kept")));
+    assert!(!workspace.is_excluded(&dir, &dir.join("crates/a")));
+
+    let mut members = manifest
+        .members(&dir)
+        .unwrap()
+        .into_keys()
+        .collect::<Vec<_>>();
+    members.sort();
+    assert_eq!(members, [dir.join("crates/a"), dir.join("vendor/kept")]);
+
+    // Excluded packages are their own workspace root
+    let find_workspace = |package: &str| {
+        let manifest = utils::find_manifest(&dir.join(package)).unwrap();
+        utils::find_workspace(&manifest)
+            .unwrap()
+            .map(|(path, _)| path)
+    };
+    assert_eq!(find_workspace("crates/a"), Some(manifest_path));
+    assert_eq!(find_workspace("crates/excluded"), None);
+    assert_eq!(find_workspace("vendor/other"), None);
+}
+
+#[test]
+fn test_path_dependency_members() {
+    let dir = crate::utils::create_test_dir(
+        "path-dependency-members",
+        &[
+            (
+                "ws/Cargo.toml",
+                r#"
+[package]
+name = "root"
+
+[dependencies]
+d = { path = "d" }
+
+[workspace]
+members = ["a"]
+
+[workspace.dependencies]
+c = { path = "c" }
+"#,
+            ),
+            (
+                "ws/a/Cargo.toml",
+                r#"
+[package]
+name = "a"
+
+[dependencies]
+b = { path = "../b" }
+outside = { path = "../../outside" }
+serde = "1"
+"#,
+            ),
+            (
+                "ws/b/Cargo.toml",
+                "[package]\nname = \"b\"\n[target.'cfg(unix)'.dev-dependencies]\nc = { workspace = true }",
+            ),
+            ("ws/c/Cargo.toml", "[package]\nname = \"c\""),
+            ("ws/d/Cargo.toml", "[package]\nname = \"d\""),
+            ("outside/Cargo.toml", "[package]\nname = \"outside\""),
+        ],
+    );
+    let ws = dir.join("ws");
+    let (_, manifest) = utils::find_manifest(&ws).unwrap();
+
+    // Path dependencies within the workspace root are members, recursively and including
+    // those inherited from `[workspace.dependencies]`
+    let mut members = manifest
+        .members(&ws)
+        .unwrap()
+        .into_keys()
+        .collect::<Vec<_>>();
+    members.sort();
+    assert_eq!(members, ["a", "b", "c", "d"].map(|member| ws.join(member)));
 }
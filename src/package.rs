@@ -0,0 +1,651 @@
+use crate::args::Args;
+use crate::artifact::{Artifact, ArtifactType};
+use crate::error::{Error, Result};
+use crate::manifest::{CrateType, Manifest, WorkspacePackage};
+use crate::utils;
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// A package selected by [`Subcommand`][crate::Subcommand], together with the
+/// artifacts that were discovered for it and filtered by the target selection
+/// options in [`Args`].
+#[derive(Debug)]
+pub struct Package {
+    name: String,
+    manifest: PathBuf,
+    fields: WorkspacePackage,
+    metadata: Option<toml::Value>,
+    workspace_metadata: Option<toml::Value>,
+    lib_artifact: Option<Artifact>,
+    bin_artifacts: Vec<Artifact>,
+    example_artifacts: Vec<Artifact>,
+    test_artifacts: Vec<Artifact>,
+    bench_artifacts: Vec<Artifact>,
+}
+
+impl Package {
+    pub(crate) fn new(
+        args: &Args,
+        manifest_path: PathBuf,
+        manifest: &Manifest,
+        workspace_manifest: Option<&(PathBuf, Manifest)>,
+    ) -> Result<Self> {
+        // The manifest is known to contain a package at this point
+        let package_table = manifest.package.as_ref().unwrap();
+        let package = &package_table.name;
+
+        let root_dir = manifest_path.parent().unwrap();
+
+        // Resolve fields that are inherited from `[workspace.package]` of the root manifest
+        let (workspace_root, inherited) = match workspace_manifest {
+            Some((path, manifest)) => (
+                path.parent().unwrap(),
+                manifest.workspace.as_ref().and_then(|w| w.package.clone()),
+            ),
+            None => (root_dir, None),
+        };
+        let fields = package_table.inherit(
+            &manifest_path,
+            workspace_root,
+            &inherited.unwrap_or_default(),
+        )?;
+
+        // https://doc.rust-lang.org/cargo/reference/cargo-targets.html#target-auto-discovery
+
+        let main_bin_path = Path::new("src/main.rs");
+        let main_lib_path = Path::new("src/lib.rs");
+
+        let mut bin_artifacts = HashMap::new();
+        let mut example_artifacts = HashMap::new();
+        let mut test_artifacts = HashMap::new();
+        let mut bench_artifacts = HashMap::new();
+
+        fn find_main_file(dir: &Path, name: &str) -> Option<PathBuf> {
+            let alt_path = dir.join(format!("{name}.rs"));
+            alt_path.is_file().then_some(alt_path).or_else(|| {
+                let alt_path = dir.join(name).join("main.rs");
+                alt_path.is_file().then_some(alt_path)
+            })
+        }
+
+        /// The directory in which targets of `r#type` are looked up by name
+        fn target_dir(r#type: ArtifactType) -> &'static str {
+            match r#type {
+                ArtifactType::Bin => "src/bin",
+                ArtifactType::Example => "examples",
+                ArtifactType::Test => "tests",
+                ArtifactType::Bench => "benches",
+                ArtifactType::Lib => unreachable!("libraries are not looked up by name"),
+            }
+        }
+
+        fn duplicate_error(r#type: ArtifactType, name: String) -> Error {
+            match r#type {
+                ArtifactType::Bin => Error::DuplicateBin(name),
+                ArtifactType::Example => Error::DuplicateExample(name),
+                ArtifactType::Test => Error::DuplicateTest(name),
+                ArtifactType::Bench => Error::DuplicateBench(name),
+                ArtifactType::Lib => unreachable!("a package has at most one library"),
+            }
+        }
+
+        /// Inserts an explicitly configured target, whose path defaults to `<name>.rs` or
+        /// `<name>/main.rs` in the `target_dir()` of its type
+        fn insert_configured(
+            name: &str,
+            path: Option<&PathBuf>,
+            r#type: ArtifactType,
+            crate_types: Vec<CrateType>,
+            harness: bool,
+            root_dir: &Path,
+            artifacts: &mut HashMap<String, Artifact>,
+        ) -> Result<()> {
+            let path = path
+                .cloned()
+                .or_else(|| find_main_file(&root_dir.join(target_dir(r#type)), name))
+                .ok_or_else(|| {
+                    let name = name.to_owned();
+                    match r#type {
+                        ArtifactType::Bin => Error::BinNotFound(name),
+                        ArtifactType::Example => Error::ExampleNotFound(name),
+                        ArtifactType::Test => Error::TestNotFound(name),
+                        ArtifactType::Bench => Error::BenchNotFound(name),
+                        ArtifactType::Lib => unreachable!("libraries are not looked up by name"),
+                    }
+                })?;
+
+            let prev = artifacts.insert(
+                name.to_owned(),
+                Artifact {
+                    name: name.to_owned(),
+                    path,
+                    r#type,
+                    crate_types,
+                    harness,
+                },
+            );
+            match prev {
+                Some(_) => Err(duplicate_error(r#type, name.to_owned())),
+                None => Ok(()),
+            }
+        }
+
+        // Add all explicitly configured binaries, examples, integration tests and benchmarks
+        for bin in &manifest.bins {
+            insert_configured(
+                &bin.name,
+                bin.path.as_ref(),
+                ArtifactType::Bin,
+                vec![CrateType::Bin],
+                true,
+                root_dir,
+                &mut bin_artifacts,
+            )?;
+        }
+        for example in &manifest.examples {
+            insert_configured(
+                &example.name,
+                example.path.as_ref(),
+                ArtifactType::Example,
+                example
+                    .crate_type
+                    .clone()
+                    .unwrap_or_else(|| vec![CrateType::Bin]),
+                true,
+                root_dir,
+                &mut example_artifacts,
+            )?;
+        }
+        for test in &manifest.tests {
+            insert_configured(
+                &test.name,
+                test.path.as_ref(),
+                ArtifactType::Test,
+                vec![CrateType::Bin],
+                test.harness,
+                root_dir,
+                &mut test_artifacts,
+            )?;
+        }
+        for bench in &manifest.benches {
+            insert_configured(
+                &bench.name,
+                bench.path.as_ref(),
+                ArtifactType::Bench,
+                vec![CrateType::Bin],
+                bench.harness,
+                root_dir,
+                &mut bench_artifacts,
+            )?;
+        }
+
+        /// Inserts an auto-discovered target unless it is explicitly configured, and returns
+        /// whether it was inserted
+        fn insert_if_unconfigured(
+            name: String,
+            path: &Path,
+            r#type: ArtifactType,
+            artifacts: &mut HashMap<String, Artifact>,
+        ) -> bool {
+            // Silently skip the detected target if another artifact already configures this
+            // file path, or if an artifact with the same name was configured
+            if artifacts.values().any(|artifact| artifact.path == path)
+                || artifacts.contains_key(&name)
+            {
+                return false;
+            }
+            artifacts.insert(
+                name.clone(),
+                Artifact {
+                    name,
+                    path: path.to_owned(),
+                    r#type,
+                    // Auto-discovered targets are executables, and tests and benchmarks use
+                    // the libtest harness
+                    crate_types: vec![CrateType::Bin],
+                    harness: true,
+                },
+            );
+            true
+        }
+
+        /// Inserts all auto-discovered `targets` that are not explicitly configured, where
+        /// like in cargo it is an error for the remaining ones to share a name, for example
+        /// when both `src/bin/<name>.rs` and `src/bin/<name>/main.rs` exist.
+        fn insert_discovered(
+            targets: Vec<(String, PathBuf)>,
+            root_dir: &Path,
+            r#type: ArtifactType,
+            artifacts: &mut HashMap<String, Artifact>,
+        ) -> Result<()> {
+            let mut discovered = HashSet::new();
+            for (name, path) in targets {
+                if discovered.contains(&name) {
+                    return Err(duplicate_error(r#type, name));
+                }
+                let path = path.strip_prefix(root_dir).unwrap_or(&path);
+                if insert_if_unconfigured(name.clone(), path, r#type, artifacts) {
+                    discovered.insert(name);
+                }
+            }
+            Ok(())
+        }
+
+        // Parse all autobins
+        if manifest.package.as_ref().is_none_or(|p| p.autobins) {
+            let mut bins = vec![];
+            // Special-case for the main binary of a package, which is named after it
+            if root_dir.join(main_bin_path).is_file() {
+                bins.push((package.clone(), main_bin_path.to_owned()));
+            }
+            bins.extend(utils::list_rust_targets(&root_dir.join("src").join("bin"))?);
+
+            insert_discovered(bins, root_dir, ArtifactType::Bin, &mut bin_artifacts)?;
+        }
+
+        // Parse all autoexamples
+        if manifest.package.as_ref().is_none_or(|p| p.autoexamples) {
+            let examples = utils::list_rust_targets(&root_dir.join("examples"))?;
+            insert_discovered(
+                examples,
+                root_dir,
+                ArtifactType::Example,
+                &mut example_artifacts,
+            )?;
+        }
+
+        // Parse all autotests
+        if manifest.package.as_ref().is_none_or(|p| p.autotests) {
+            let tests = utils::list_rust_targets(&root_dir.join("tests"))?;
+            insert_discovered(tests, root_dir, ArtifactType::Test, &mut test_artifacts)?;
+        }
+
+        // Parse all autobenches
+        if manifest.package.as_ref().is_none_or(|p| p.autobenches) {
+            let benches = utils::list_rust_targets(&root_dir.join("benches"))?;
+            insert_discovered(benches, root_dir, ArtifactType::Bench, &mut bench_artifacts)?;
+        }
+
+        let mut lib_artifact = manifest
+            .lib
+            .as_ref()
+            .map(|lib| Artifact {
+                // The library is either configured with sensible defaults
+                name: lib.name.as_ref().unwrap_or(package).clone(),
+                path: lib.path.as_deref().unwrap_or(main_lib_path).to_owned(),
+                r#type: ArtifactType::Lib,
+                crate_types: match &lib.crate_type {
+                    Some(crate_types) => crate_types.clone(),
+                    None if lib.proc_macro => vec![CrateType::ProcMacro],
+                    None => vec![CrateType::Lib],
+                },
+                harness: lib.harness,
+            })
+            .or_else(|| {
+                // Or autodetected with the same defaults, if that default path exists
+                root_dir.join(main_lib_path).is_file().then(|| Artifact {
+                    name: package.clone(),
+                    path: main_lib_path.to_owned(),
+                    r#type: ArtifactType::Lib,
+                    crate_types: vec![CrateType::Lib],
+                    harness: true,
+                })
+            });
+
+        // Filtering based on arguments
+        // https://doc.rust-lang.org/cargo/reference/cargo-targets.html#binaries
+
+        if args.specific_target_selected() && !args.all_targets {
+            if !args.lib {
+                lib_artifact = None;
+            }
+
+            if !args.bins {
+                bin_artifacts.retain(|a, _| args.bin.contains(a));
+            }
+
+            if !args.examples {
+                example_artifacts.retain(|a, _| args.example.contains(a));
+            }
+
+            if !args.tests {
+                test_artifacts.retain(|a, _| args.test.contains(a));
+            }
+
+            if !args.benches {
+                bench_artifacts.retain(|a, _| args.bench.contains(a));
+            }
+        }
+
+        Ok(Self {
+            name: package.clone(),
+            manifest: manifest_path,
+            fields,
+            metadata: package_table.metadata.clone(),
+            workspace_metadata: workspace_manifest
+                .and_then(|(_, manifest)| manifest.workspace.as_ref()?.metadata.clone()),
+            lib_artifact,
+            bin_artifacts: bin_artifacts.into_values().collect(),
+            example_artifacts: example_artifacts.into_values().collect(),
+            test_artifacts: test_artifacts.into_values().collect(),
+            bench_artifacts: bench_artifacts.into_values().collect(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn manifest(&self) -> &Path {
+        &self.manifest
+    }
+
+    /// The package version, or `0.0.0` when it is not specified
+    pub fn version(&self) -> &str {
+        self.fields.version.as_deref().unwrap_or("0.0.0")
+    }
+
+    pub fn authors(&self) -> &[String] {
+        self.fields.authors.as_deref().unwrap_or_default()
+    }
+
+    /// The Rust edition, which defaults to `2015` when it is not specified
+    pub fn edition(&self) -> &str {
+        self.fields.edition.as_deref().unwrap_or("2015")
+    }
+
+    pub fn rust_version(&self) -> Option<&str> {
+        self.fields.rust_version.as_deref()
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.fields.description.as_deref()
+    }
+
+    pub fn documentation(&self) -> Option<&str> {
+        self.fields.documentation.as_deref()
+    }
+
+    pub fn homepage(&self) -> Option<&str> {
+        self.fields.homepage.as_deref()
+    }
+
+    pub fn repository(&self) -> Option<&str> {
+        self.fields.repository.as_deref()
+    }
+
+    pub fn license(&self) -> Option<&str> {
+        self.fields.license.as_deref()
+    }
+
+    /// Path to the license file, made absolute against the package or (when
+    /// inherited) the workspace root
+    pub fn license_file(&self) -> Option<&Path> {
+        self.fields.license_file.as_deref()
+    }
+
+    pub fn keywords(&self) -> &[String] {
+        self.fields.keywords.as_deref().unwrap_or_default()
+    }
+
+    pub fn categories(&self) -> &[String] {
+        self.fields.categories.as_deref().unwrap_or_default()
+    }
+
+    /// Deserializes the [`package.metadata.<tool>`] table of this package, if it exists.
+    ///
+    /// [`package.metadata.<tool>`]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-metadata-table
+    pub fn metadata<T: DeserializeOwned>(&self, tool: &str) -> Result<Option<T>> {
+        self.deserialize_metadata(self.metadata.as_ref().and_then(|m| m.get(tool)).cloned())
+    }
+
+    /// Deserializes the [`package.metadata.<tool>`] table of this package merged over
+    /// the [`workspace.metadata.<tool>`] table of its workspace root, where values from
+    /// the package take precedence.
+    ///
+    /// [`package.metadata.<tool>`]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-metadata-table
+    /// [`workspace.metadata.<tool>`]: https://doc.rust-lang.org/cargo/reference/workspaces.html#the-metadata-table
+    pub fn merged_metadata<T: DeserializeOwned>(&self, tool: &str) -> Result<Option<T>> {
+        let get = |metadata: &Option<toml::Value>| metadata.as_ref()?.get(tool).cloned();
+        let metadata = match (get(&self.workspace_metadata), get(&self.metadata)) {
+            (Some(workspace), Some(package)) => Some(utils::merge_toml(workspace, package)),
+            (workspace, package) => package.or(workspace),
+        };
+        self.deserialize_metadata(metadata)
+    }
+
+    fn deserialize_metadata<T: DeserializeOwned>(
+        &self,
+        metadata: Option<toml::Value>,
+    ) -> Result<Option<T>> {
+        metadata
+            .map(|metadata| {
+                metadata
+                    .try_into()
+                    .map_err(|e| Error::Toml(self.manifest.clone(), e))
+            })
+            .transpose()
+    }
+
+    pub fn lib_artifact(&self) -> Option<&Artifact> {
+        self.lib_artifact.as_ref()
+    }
+
+    pub fn bin_artifacts(&self) -> &[Artifact] {
+        &self.bin_artifacts
+    }
+
+    pub fn example_artifacts(&self) -> &[Artifact] {
+        &self.example_artifacts
+    }
+
+    pub fn test_artifacts(&self) -> &[Artifact] {
+        &self.test_artifacts
+    }
+
+    pub fn bench_artifacts(&self) -> &[Artifact] {
+        &self.bench_artifacts
+    }
+
+    pub fn artifacts(&self) -> impl Iterator<Item = &Artifact> {
+        self.lib_artifact
+            .iter()
+            .chain(&self.bin_artifacts)
+            .chain(&self.example_artifacts)
+            .chain(&self.test_artifacts)
+            .chain(&self.bench_artifacts)
+    }
+}
+
+/// Creates [`Package`] from the manifest in `dir`, with the target selection options in `args`
+#[cfg(all(test, feature = "clap"))]
+fn test_package(dir: &Path, args: &[&str]) -> Result<Package> {
+    use clap::Parser;
+    let args = Args::parse_from(std::iter::once("cargo-subcommand").chain(args.iter().copied()));
+    let manifest_path = dir.join("Cargo.toml");
+    let manifest = Manifest::parse_from_toml(&manifest_path)?;
+    Package::new(&args, manifest_path, &manifest, None)
+}
+
+/// The sorted names of `artifacts`
+#[cfg(all(test, feature = "clap"))]
+fn artifact_names(artifacts: &[Artifact]) -> Vec<&str> {
+    let mut names = artifacts
+        .iter()
+        .map(|artifact| artifact.name.as_str())
+        .collect::<Vec<_>>();
+    names.sort_unstable();
+    names
+}
+
+#[test]
+#[cfg(feature = "clap")]
+fn test_test_and_bench_discovery() {
+    let dir = utils::create_test_dir(
+        "test-and-bench-discovery",
+        &[
+            (
+                "Cargo.toml",
+                "[package]\nname = \"pkg\"\n\
+                 [[test]]\nname = \"custom\"\npath = \"checks/custom.rs\"\nharness = false",
+            ),
+            ("src/lib.rs", ""),
+            ("src/main.rs", ""),
+            ("tests/it.rs", ""),
+            ("tests/nested/main.rs", ""),
+            ("tests/data/input.txt", ""),
+            ("checks/custom.rs", ""),
+            ("benches/perf.rs", ""),
+            ("examples/demo.rs", ""),
+        ],
+    );
+
+    // Without target selection options all targets are discovered
+    let package = test_package(&dir, &[]).unwrap();
+    assert_eq!(
+        artifact_names(package.test_artifacts()),
+        ["custom", "it", "nested"]
+    );
+    assert_eq!(artifact_names(package.bench_artifacts()), ["perf"]);
+    let test = |name: &str| {
+        package
+            .test_artifacts()
+            .iter()
+            .find(|a| a.name == name)
+            .unwrap()
+    };
+    assert_eq!(test("it").path, Path::new("tests/it.rs"));
+    assert_eq!(test("nested").path, Path::new("tests/nested/main.rs"));
+    assert!(test("it").harness);
+    assert_eq!(test("custom").path, Path::new("checks/custom.rs"));
+    assert!(!test("custom").harness);
+    assert_eq!(package.bench_artifacts()[0].r#type, ArtifactType::Bench);
+
+    // Selecting a single test drops all other targets
+    let package = test_package(&dir, &["--test", "it"]).unwrap();
+    assert_eq!(artifact_names(package.test_artifacts()), ["it"]);
+    assert!(package.bench_artifacts().is_empty());
+    assert!(package.bin_artifacts().is_empty());
+    assert!(package.lib_artifact().is_none());
+
+    let package = test_package(&dir, &["--tests"]).unwrap();
+    assert_eq!(
+        artifact_names(package.test_artifacts()),
+        ["custom", "it", "nested"]
+    );
+    assert!(package.bench_artifacts().is_empty());
+
+    let package = test_package(&dir, &["--bench", "perf", "--lib"]).unwrap();
+    assert_eq!(artifact_names(package.bench_artifacts()), ["perf"]);
+    assert!(package.test_artifacts().is_empty());
+    assert!(package.lib_artifact().is_some());
+
+    let package = test_package(&dir, &["--benches", "--test", "custom"]).unwrap();
+    assert_eq!(artifact_names(package.bench_artifacts()), ["perf"]);
+    assert_eq!(artifact_names(package.test_artifacts()), ["custom"]);
+    assert!(package.example_artifacts().is_empty());
+
+    // `--all-targets` overrides any other selection
+    let package = test_package(&dir, &["--all-targets", "--test", "it"]).unwrap();
+    assert_eq!(
+        artifact_names(package.test_artifacts()),
+        ["custom", "it", "nested"]
+    );
+    assert_eq!(artifact_names(package.bench_artifacts()), ["perf"]);
+    assert_eq!(artifact_names(package.example_artifacts()), ["demo"]);
+    assert_eq!(artifact_names(package.bin_artifacts()), ["pkg"]);
+    assert!(package.lib_artifact().is_some());
+
+    // Tests and benchmarks are not uplifted, so their path cannot be known
+    use clap::Parser;
+    let args = Args::parse_from([
+        "cargo-subcommand",
+        "--manifest-path",
+        dir.join("Cargo.toml").to_str().unwrap(),
+    ]);
+    let subcommand = crate::Subcommand::new(args).unwrap();
+    let package = &subcommand.packages()[0];
+    for artifact in package
+        .test_artifacts()
+        .iter()
+        .chain(package.bench_artifacts())
+    {
+        assert!(matches!(
+            subcommand.artifact(artifact, None, CrateType::Bin),
+            Err(Error::UnupliftedArtifact { name, artifact: r#type })
+                if name == artifact.name && r#type == artifact.r#type
+        ));
+    }
+    let bin = &package.bin_artifacts()[0];
+    assert!(subcommand.artifact(bin, None, CrateType::Bin).is_ok());
+}
+
+#[test]
+#[cfg(feature = "clap")]
+fn test_bin_conflicts() {
+    // A binary cannot be discovered both as a file and as a directory
+    let dir = utils::create_test_dir(
+        "bin-file-and-dir",
+        &[
+            ("Cargo.toml", "[package]\nname = \"pkg\""),
+            ("src/bin/foo.rs", ""),
+            ("src/bin/foo/main.rs", ""),
+        ],
+    );
+    assert!(matches!(
+        test_package(&dir, &[]),
+        Err(Error::DuplicateBin(name)) if name == "foo"
+    ));
+
+    // Nor can it shadow the main binary, which is named after the package
+    let dir = utils::create_test_dir(
+        "bin-main-and-named",
+        &[
+            ("Cargo.toml", "[package]\nname = \"pkg\""),
+            ("src/main.rs", ""),
+            ("src/bin/pkg.rs", ""),
+        ],
+    );
+    assert!(matches!(
+        test_package(&dir, &[]),
+        Err(Error::DuplicateBin(name)) if name == "pkg"
+    ));
+
+    // An explicitly configured binary takes precedence over the discovered ones
+    let dir = utils::create_test_dir(
+        "bin-configured-dir",
+        &[
+            (
+                "Cargo.toml",
+                "[package]\nname = \"pkg\"\n\
+                 [[bin]]\nname = \"foo\"\npath = \"src/bin/foo/main.rs\"",
+            ),
+            ("src/bin/foo.rs", ""),
+            ("src/bin/foo/main.rs", ""),
+        ],
+    );
+    let package = test_package(&dir, &[]).unwrap();
+    assert_eq!(artifact_names(package.bin_artifacts()), ["foo"]);
+    assert_eq!(
+        package.bin_artifacts()[0].path,
+        Path::new("src/bin/foo/main.rs")
+    );
+
+    // Also when it configures a directory target under another name, which is then not
+    // discovered under its own name
+    let dir = utils::create_test_dir(
+        "bin-renamed-dir",
+        &[
+            (
+                "Cargo.toml",
+                "[package]\nname = \"pkg\"\n\
+                 [[bin]]\nname = \"bar\"\npath = \"src/bin/foo/main.rs\"",
+            ),
+            ("src/bin/foo/main.rs", ""),
+            ("src/bin/baz.rs", ""),
+        ],
+    );
+    let package = test_package(&dir, &[]).unwrap();
+    assert_eq!(artifact_names(package.bin_artifacts()), ["bar", "baz"]);
+}
@@ -1,44 +1,35 @@
 use crate::args::Args;
-use crate::artifact::{Artifact, ArtifactType};
+use crate::artifact::{Artifact, ArtifactType, FileFlavor};
+use crate::config::{Definition, ResolvedTarget, Value};
 use crate::error::{Error, Result};
-use crate::manifest::Manifest;
+use crate::flags::Flags;
+use crate::package::Package;
 use crate::profile::Profile;
-use crate::{utils, CrateType, LocalizedConfig};
-use std::collections::HashMap;
+use crate::{utils, CrateType, MergedConfig};
+use serde::de::DeserializeOwned;
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug)]
 pub struct Subcommand {
     args: Args,
-    package: String,
+    packages: Vec<Package>,
     workspace_manifest: Option<PathBuf>,
-    manifest: PathBuf,
+    workspace_metadata: Option<toml::Value>,
     target_dir: PathBuf,
+    target_dir_definition: Option<Definition>,
+    targets: Vec<String>,
+    target_definitions: Vec<Definition>,
     host_triple: String,
     profile: Profile,
-    lib_artifact: Option<Artifact>,
-    bin_artifacts: Vec<Artifact>,
-    example_artifacts: Vec<Artifact>,
-    config: Option<LocalizedConfig>,
+    config: MergedConfig,
 }
 
 impl Subcommand {
     pub fn new(args: Args) -> Result<Self> {
-        // TODO: support multiple packages properly
-        assert!(
-            args.package.len() < 2,
-            "Multiple packages are not supported yet by `cargo-subcommand`"
-        );
-        let package = args.package.first().map(|s| s.as_str());
-        assert!(
-            !args.workspace,
-            "`--workspace` is not supported yet by `cargo-subcommand`"
-        );
-        assert!(
-            args.exclude.is_empty(),
-            "`--exclude` is not supported yet by `cargo-subcommand`"
-        );
+        if !args.exclude.is_empty() && !args.workspace {
+            return Err(Error::ExcludeWithoutWorkspace);
+        }
 
         let manifest_path = args
             .manifest_path
@@ -59,226 +50,96 @@ impl Subcommand {
 
         // Scan up the directories based on --manifest-path and the working directory to find a Cargo.toml
         let potential_manifest = utils::find_manifest(&search_path)?;
-        // Perform the same scan, but for a Cargo.toml containing [workspace]
-        let workspace_manifest = utils::find_workspace(&search_path)?;
-
-        let (manifest_path, manifest) = {
-            if let Some(workspace_manifest) = &workspace_manifest {
-                utils::find_package_manifest_in_workspace(
-                    workspace_manifest,
-                    potential_manifest,
-                    package,
-                )?
+        // Perform the same scan, but for a Cargo.toml containing [workspace] that does not exclude
+        // it, unless the package explicitly points to its workspace root
+        let workspace_manifest = utils::find_workspace(&potential_manifest)?;
+
+        let packages = if let Some(workspace_manifest) = &workspace_manifest {
+            utils::find_package_manifest_in_workspace(
+                workspace_manifest,
+                potential_manifest,
+                &args.package,
+                args.workspace,
+            )?
+        } else {
+            // A package outside of a workspace is the only member of its own implicit workspace
+            let names = if args.workspace {
+                &[][..]
             } else {
-                let (manifest_path, manifest) = potential_manifest;
-                manifest.map_nonvirtual_package(manifest_path, package)?
-            }
+                &args.package
+            };
+            let (manifest_path, manifest) = potential_manifest;
+            vec![manifest.map_nonvirtual_package(manifest_path, names)?]
         };
 
-        // The manifest is known to contain a package at this point
-        let package = &manifest.package.as_ref().unwrap().name;
-
-        let root_dir = manifest_path.parent().unwrap();
-
-        // TODO: Find, parse, and merge _all_ config files following the hierarchical structure:
-        // https://doc.rust-lang.org/cargo/reference/config.html#hierarchical-structure
-        let config = LocalizedConfig::find_cargo_config_for_workspace(root_dir)?;
-        if let Some(config) = &config {
-            config.set_env_vars().unwrap();
-        }
-
-        let parsed_manifest = Manifest::parse_from_toml(&manifest_path)?;
-
-        let target_dir = args
-            .target_dir
-            .clone()
-            .or_else(|| {
-                std::env::var_os("CARGO_BUILD_TARGET_DIR")
-                    .or_else(|| std::env::var_os("CARGO_TARGET_DIR"))
-                    .map(|os_str| os_str.into())
-            })
-            .map(|target_dir| {
-                if target_dir.is_relative() {
-                    std::env::current_dir().unwrap().join(target_dir)
-                } else {
-                    target_dir
-                }
-            });
-
-        let target_dir = target_dir.unwrap_or_else(|| {
-            workspace_manifest
-                .as_ref()
-                .map(|(path, _)| path)
-                .unwrap_or_else(|| &manifest_path)
-                .parent()
-                .unwrap()
-                .join(utils::get_target_dir_name(config.as_deref()).unwrap())
-        });
-
-        // https://doc.rust-lang.org/cargo/reference/cargo-targets.html#target-auto-discovery
-
-        let main_bin_path = Path::new("src/main.rs");
-        let main_lib_path = Path::new("src/lib.rs");
-
-        let mut bin_artifacts = HashMap::new();
-        let mut example_artifacts = HashMap::new();
-
-        fn find_main_file(dir: &Path, name: &str) -> Option<PathBuf> {
-            let alt_path = dir.join(format!("{name}.rs"));
-            alt_path.is_file().then_some(alt_path).or_else(|| {
-                let alt_path = dir.join(name).join("main.rs");
-                alt_path.is_file().then_some(alt_path)
-            })
-        }
-
-        // Add all explicitly configured binaries
-        for bin in &parsed_manifest.bins {
-            let path = bin
-                .path
-                .clone()
-                .or_else(|| find_main_file(&root_dir.join("src/bin"), &bin.name))
-                .ok_or_else(|| Error::BinNotFound(bin.name.clone()))?;
-
-            let prev = bin_artifacts.insert(
-                bin.name.clone(),
-                Artifact {
-                    name: bin.name.clone(),
-                    path,
-                    r#type: ArtifactType::Bin,
-                },
-            );
-            if prev.is_some() {
-                return Err(Error::DuplicateBin(bin.name.clone()));
-            }
-        }
-
-        // Add all explicitly configured examples
-        for example in &parsed_manifest.examples {
-            let path = example
-                .path
-                .clone()
-                .or_else(|| find_main_file(&root_dir.join("examples"), &example.name))
-                .ok_or_else(|| Error::ExampleNotFound(example.name.clone()))?;
-
-            let prev = example_artifacts.insert(
-                example.name.clone(),
-                Artifact {
-                    name: example.name.clone(),
-                    path,
-                    r#type: ArtifactType::Example,
-                },
-            );
-            if prev.is_some() {
-                return Err(Error::DuplicateExample(example.name.clone()));
-            }
-        }
-
-        /// Name is typically the [`Path::file_stem()`], except for `src/main.rs` where it is the package name
-        fn insert_if_unconfigured(
-            name: Option<String>,
-            path: &Path,
-            r#type: ArtifactType,
-            artifacts: &mut HashMap<String, Artifact>,
-        ) {
-            // Only insert the detected binary if there isn't another artifact already configuring this file path
-            if artifacts.values().any(|bin| bin.path == path) {
-                println!("Already configuring {path:?}");
-                return;
-            }
-
-            let name =
-                name.unwrap_or_else(|| path.file_stem().unwrap().to_str().unwrap().to_owned());
-
-            // Only insert the detected binary if an artifact with the same name wasn't yet configured
-            artifacts.entry(name.clone()).or_insert(Artifact {
-                name,
-                path: path.to_owned(),
-                r#type,
-            });
-        }
-
-        // Parse all autobins
-        if parsed_manifest.package.as_ref().is_none_or(|p| p.autobins) {
-            // Special-case for the main binary of a package
-            if root_dir.join(main_bin_path).is_file() {
-                insert_if_unconfigured(
-                    Some(package.clone()),
-                    main_bin_path,
-                    ArtifactType::Bin,
-                    &mut bin_artifacts,
-                );
-            }
-
-            for file in utils::list_rust_files(&root_dir.join("src").join("bin"))? {
-                let file = file.strip_prefix(root_dir).unwrap();
-
-                insert_if_unconfigured(None, file, ArtifactType::Bin, &mut bin_artifacts);
-            }
-        }
-
-        // Parse all autoexamples
-        if parsed_manifest
-            .package
+        // The root manifest of the (possibly implicit) workspace
+        let root_manifest = workspace_manifest
             .as_ref()
-            .is_none_or(|p| p.autoexamples)
-        {
-            for file in utils::list_rust_files(&root_dir.join("examples"))? {
-                let file = file.strip_prefix(root_dir).unwrap();
+            .map_or(&packages[0].0, |(path, _)| path)
+            .clone();
 
-                insert_if_unconfigured(None, file, ArtifactType::Example, &mut example_artifacts);
-            }
-        }
-
-        let mut lib_artifact = parsed_manifest
-            .lib
-            .as_ref()
-            .map(|lib| Artifact {
-                // The library is either configured with sensible defaults
-                name: lib.name.as_ref().unwrap_or(package).clone(),
-                path: lib.path.as_deref().unwrap_or(main_lib_path).to_owned(),
-                r#type: ArtifactType::Lib,
-            })
-            .or_else(|| {
-                // Or autodetected with the same defaults, if that default path exists
-                root_dir.join(main_lib_path).is_file().then(|| Artifact {
-                    name: package.clone(),
-                    path: main_lib_path.to_owned(),
-                    r#type: ArtifactType::Lib,
-                })
-            });
-
-        // Filtering based on arguments
-        // https://doc.rust-lang.org/cargo/reference/cargo-targets.html#binaries
-
-        let specific_target_selected = args.specific_target_selected();
+        let packages = if args.exclude.is_empty() {
+            packages
+        } else {
+            utils::exclude_packages(&root_manifest, packages, &args.exclude)?
+        };
 
-        if specific_target_selected {
-            if !args.lib {
-                lib_artifact = None;
-            }
+        // Like cargo, merge all config files from the working directory up to the filesystem root
+        let cwd = std::env::current_dir().map_err(|e| Error::Io(PathBuf::new(), e))?;
+        let config = MergedConfig::find(&cwd)?.with_cli_args(&args.config, &cwd)?;
+
+        // `CARGO_TARGET_DIR` takes precedence over `build.target-dir` from any config source,
+        // which in turn includes `--config` arguments and `CARGO_BUILD_TARGET_DIR`
+        let target_dir = match args.target_dir.clone() {
+            Some(target_dir) => Some(Value {
+                val: cwd.join(target_dir),
+                definition: Definition::Flag("--target-dir"),
+            }),
+            None => match std::env::var_os("CARGO_TARGET_DIR") {
+                Some(target_dir) => Some(Value {
+                    val: cwd.join(target_dir),
+                    definition: Definition::Environment("CARGO_TARGET_DIR".to_owned()),
+                }),
+                None => config.target_dir()?,
+            },
+        };
+        let (target_dir, target_dir_definition) = match target_dir {
+            Some(Value { val, definition }) => (val, Some(definition)),
+            None => (root_manifest.parent().unwrap().join("target"), None),
+        };
 
-            if !args.bins {
-                bin_artifacts.retain(|a, _| args.bin.contains(a));
-            }
+        // `--target` takes precedence over `build.target`, which may list multiple targets
+        let (targets, target_definitions) = match &args.target {
+            Some(target) => (vec![target.clone()], vec![Definition::Flag("--target")]),
+            None => config
+                .build_target()?
+                .into_iter()
+                .map(|Value { val, definition }| (val, definition))
+                .unzip(),
+        };
 
-            if !args.examples {
-                example_artifacts.retain(|a, _| args.example.contains(a));
-            }
-        }
+        let packages = packages
+            .into_iter()
+            .map(|(manifest_path, manifest)| {
+                Package::new(&args, manifest_path, &manifest, workspace_manifest.as_ref())
+            })
+            .collect::<Result<Vec<_>>>()?;
 
         let host_triple = current_platform::CURRENT_PLATFORM.to_owned();
         let profile = args.profile();
         Ok(Self {
             args,
-            package: package.clone(),
+            packages,
+            workspace_metadata: workspace_manifest
+                .as_ref()
+                .and_then(|(_, manifest)| manifest.workspace.as_ref()?.metadata.clone()),
             workspace_manifest: workspace_manifest.map(|(path, _)| path),
-            manifest: manifest_path,
             target_dir,
+            target_dir_definition,
+            targets,
+            target_definitions,
             host_triple,
             profile,
-            lib_artifact,
-            bin_artifacts: bin_artifacts.into_values().collect(),
-            example_artifacts: example_artifacts.into_values().collect(),
             config,
         })
     }
@@ -287,37 +148,79 @@ impl Subcommand {
         &self.args
     }
 
-    pub fn package(&self) -> &str {
-        &self.package
+    /// All packages selected by `--package` or `--workspace`, or the package in
+    /// the current directory or `--manifest-path` if none were specified.
+    pub fn packages(&self) -> &[Package] {
+        &self.packages
     }
 
     pub fn workspace_manifest(&self) -> Option<&Path> {
         self.workspace_manifest.as_deref()
     }
 
-    pub fn manifest(&self) -> &Path {
-        &self.manifest
+    /// Deserializes the [`workspace.metadata.<tool>`] table of the workspace root, if any.
+    /// See [`Package::metadata()`] and [`Package::merged_metadata()`] for package metadata.
+    ///
+    /// [`workspace.metadata.<tool>`]: https://doc.rust-lang.org/cargo/reference/workspaces.html#the-metadata-table
+    pub fn workspace_metadata<T: DeserializeOwned>(&self, tool: &str) -> Result<Option<T>> {
+        self.workspace_metadata
+            .as_ref()
+            .and_then(|metadata| metadata.get(tool))
+            .map(|metadata| {
+                metadata.clone().try_into().map_err(|e| {
+                    Error::Toml(self.workspace_manifest.clone().unwrap_or_default(), e)
+                })
+            })
+            .transpose()
     }
 
+    /// The target passed to `--target`, or else the first of [`targets()`][Self::targets()]
+    /// configured in [`build.target`].
+    ///
+    /// [`build.target`]: https://doc.rust-lang.org/cargo/reference/config.html#buildtarget
     pub fn target(&self) -> Option<&str> {
-        self.args.target.as_deref()
+        self.targets.first().map(String::as_str)
+    }
+
+    /// All targets to build for: the target passed to `--target`, or else the targets
+    /// configured in [`build.target`].  Empty when building for the host.
+    ///
+    /// [`build.target`]: https://doc.rust-lang.org/cargo/reference/config.html#buildtarget
+    pub fn targets(&self) -> &[String] {
+        &self.targets
+    }
+
+    /// Where [`target()`][Self::target()] was defined: `--target` or
+    /// [`build.target`][MergedConfig::build_target()], or [`None`] when building for the host.
+    pub fn target_definition(&self) -> Option<&Definition> {
+        self.target_definitions.first()
+    }
+
+    /// Where each of [`targets()`][Self::targets()] was defined, in the same order
+    pub fn target_definitions(&self) -> &[Definition] {
+        &self.target_definitions
     }
 
     pub fn profile(&self) -> &Profile {
         &self.profile
     }
 
+    /// Artifacts of all selected [`packages()`][Self::packages()]
     pub fn artifacts(&self) -> impl Iterator<Item = &Artifact> {
-        self.lib_artifact
-            .iter()
-            .chain(&self.bin_artifacts)
-            .chain(&self.example_artifacts)
+        self.packages.iter().flat_map(Package::artifacts)
     }
 
     pub fn target_dir(&self) -> &Path {
         &self.target_dir
     }
 
+    /// Where [`target_dir()`][Self::target_dir()] was defined: `--target-dir`,
+    /// `CARGO_TARGET_DIR` or [`build.target-dir`][MergedConfig::target_dir()], or [`None`]
+    /// when it defaults to `target` in the workspace root.
+    pub fn target_dir_definition(&self) -> Option<&Definition> {
+        self.target_dir_definition.as_ref()
+    }
+
     pub fn host_triple(&self) -> &str {
         &self.host_triple
     }
@@ -326,30 +229,96 @@ impl Subcommand {
         self.args.quiet
     }
 
-    pub fn config(&self) -> Option<&LocalizedConfig> {
-        self.config.as_ref()
+    pub fn config(&self) -> &MergedConfig {
+        &self.config
+    }
+
+    /// Resolves the [`target`] config tables for `target`, or for the host when [`None`],
+    /// by querying its configuration options from `rustc`.
+    ///
+    /// [`target`]: https://doc.rust-lang.org/cargo/reference/config.html#target
+    pub fn target_config(&self, target: Option<&str>) -> Result<ResolvedTarget> {
+        let cfgs = crate::rustc_cfgs(target)?;
+        let triple = target.map_or_else(|| self.host_triple(), utils::target_short_name);
+        self.config.target(triple, &cfgs)
+    }
+
+    /// The flags that cargo passes to `rustc` when building for `target`, or for the host
+    /// when [`None`].  Like cargo, the first of these sources that is set is used:
+    ///
+    /// 1. `CARGO_ENCODED_RUSTFLAGS`
+    /// 2. `RUSTFLAGS`
+    /// 3. All matching [`target.<triple>.rustflags` and `target.<cfg>.rustflags`][Self::target_config()]
+    /// 4. [`build.rustflags`][MergedConfig::build_rustflags()]
+    pub fn rustflags(&self, target: Option<&str>) -> Result<Flags> {
+        if let Some(flags) = Flags::from_env("RUSTFLAGS")? {
+            return Ok(flags);
+        }
+        let rustflags = self.target_config(target)?.rustflags;
+        if !rustflags.is_empty() {
+            return Ok(Flags::from_config("RUSTFLAGS", rustflags));
+        }
+        let rustflags = self.config.build_rustflags()?;
+        Ok(Flags::from_config("RUSTFLAGS", rustflags))
+    }
+
+    /// The flags that cargo passes to `rustdoc`, from `CARGO_ENCODED_RUSTDOCFLAGS`,
+    /// `RUSTDOCFLAGS` or [`build.rustdocflags`][MergedConfig::build_rustdocflags()] in
+    /// that order of precedence.
+    pub fn rustdocflags(&self) -> Result<Flags> {
+        if let Some(flags) = Flags::from_env("RUSTDOCFLAGS")? {
+            return Ok(flags);
+        }
+        let rustdocflags = self.config.build_rustdocflags()?;
+        Ok(Flags::from_config("RUSTDOCFLAGS", rustdocflags))
     }
 
     pub fn build_dir(&self, target: Option<&str>) -> PathBuf {
         let target_dir = dunce::simplified(self.target_dir());
         let arch_dir = if let Some(target) = target {
-            target_dir.join(target)
+            target_dir.join(utils::target_short_name(target))
         } else {
             target_dir.to_path_buf()
         };
         arch_dir.join(self.profile())
     }
 
+    /// Path to `artifact` when built as `crate_type` for `target`, which fails when
+    /// `crate_type` is not declared in the [`crate-type`][Artifact::crate_types] of the
+    /// artifact, or for tests and benchmarks whose file names in `deps` carry a hash
+    /// that is only known to cargo.
     pub fn artifact(
         &self,
         artifact: &Artifact,
         target: Option<&str>,
         crate_type: CrateType,
-    ) -> PathBuf {
-        let triple = target.unwrap_or_else(|| self.host_triple());
-        let file_name = artifact.file_name(crate_type, triple);
-        self.build_dir(target)
-            .join(artifact.build_dir())
-            .join(file_name)
+    ) -> Result<PathBuf> {
+        let mut files = self.artifact_files(artifact, target, crate_type)?;
+        Ok(files.swap_remove(0).0)
+    }
+
+    /// Paths to all [files][Artifact::file_names()] produced for `artifact` when built as
+    /// `crate_type` for `target`, starting with the path returned by [`Self::artifact()`].
+    pub fn artifact_files(
+        &self,
+        artifact: &Artifact,
+        target: Option<&str>,
+        crate_type: CrateType,
+    ) -> Result<Vec<(PathBuf, FileFlavor)>> {
+        if matches!(artifact.r#type, ArtifactType::Test | ArtifactType::Bench) {
+            return Err(Error::UnupliftedArtifact {
+                name: artifact.name.clone(),
+                artifact: artifact.r#type,
+            });
+        }
+        // Procedural macros are always built for the host
+        let target = target.filter(|_| crate_type != CrateType::ProcMacro);
+        let triple = target.map_or_else(|| self.host_triple(), utils::target_short_name);
+        let dir = self.build_dir(target).join(artifact.build_dir());
+        Ok(artifact
+            .file_names(crate_type, triple)?
+            .into_iter()
+            .map(|file| (dir.join(file.name), file.flavor))
+            .collect())
     }
 }
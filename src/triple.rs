@@ -0,0 +1,164 @@
+/// Vendors that may appear as the second component of a target triple, to tell
+/// `<arch>-<vendor>-<os>` apart from `<arch>-<os>-<env>` triples like `aarch64-linux-android`.
+const VENDORS: &[&str] = &[
+    "unknown",
+    "pc",
+    "apple",
+    "nvidia",
+    "sun",
+    "fortanix",
+    "wrs",
+    "uwp",
+    "kmc",
+    "nintendo",
+    "sony",
+    "espressif",
+    "ibm",
+    "unikraft",
+    "risc0",
+    "win7",
+];
+
+/// A [target triple], split into its components as far as needed to determine the names
+/// of the files that `rustc` produces for it.
+///
+/// [target triple]: https://doc.rust-lang.org/rustc/platform-support.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Triple<'a> {
+    pub arch: &'a str,
+    pub vendor: Option<&'a str>,
+    pub os: &'a str,
+    pub env: Option<&'a str>,
+}
+
+impl<'a> Triple<'a> {
+    pub fn new(triple: &'a str) -> Self {
+        let mut parts = triple.splitn(4, '-');
+        let arch = parts.next().unwrap();
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(vendor), Some(os), env @ Some(_)) => Self {
+                arch,
+                vendor: Some(vendor),
+                os,
+                env,
+            },
+            (Some(vendor), Some(os), None) if VENDORS.contains(&vendor) => Self {
+                arch,
+                vendor: Some(vendor),
+                os,
+                env: None,
+            },
+            (Some(os), env, _) => Self {
+                arch,
+                vendor: None,
+                os,
+                env,
+            },
+            (None, ..) => Self {
+                arch,
+                vendor: None,
+                os: "unknown",
+                env: None,
+            },
+        }
+    }
+
+    pub fn is_windows(&self) -> bool {
+        self.os == "windows"
+    }
+
+    pub fn is_msvc(&self) -> bool {
+        self.is_windows() && self.env.is_some_and(|env| env.starts_with("msvc"))
+    }
+
+    pub fn is_apple(&self) -> bool {
+        self.vendor == Some("apple")
+    }
+
+    pub fn is_wasm(&self) -> bool {
+        self.arch.starts_with("wasm")
+    }
+
+    pub fn is_emscripten(&self) -> bool {
+        self.os == "emscripten"
+    }
+
+    /// Whether `rustc` can split debug information into a `.dwp` package on this target
+    pub(crate) fn supports_dwp(&self) -> bool {
+        !self.is_windows() && !self.is_apple() && !self.is_wasm() && self.os != "none"
+    }
+
+    /// Suffix of executables, like `.exe` on Windows
+    pub fn exe_suffix(&self) -> &'static str {
+        if self.is_windows() {
+            ".exe"
+        } else if self.is_emscripten() {
+            ".js"
+        } else if self.is_wasm() {
+            ".wasm"
+        } else if self.os == "uefi" {
+            ".efi"
+        } else {
+            ""
+        }
+    }
+
+    /// Prefix and suffix of dynamic libraries, like `lib` and `.so` on Linux
+    pub fn dll_prefix_suffix(&self) -> (&'static str, &'static str) {
+        if self.is_windows() {
+            ("", ".dll")
+        } else if self.is_wasm() {
+            ("", ".wasm")
+        } else if self.is_apple() {
+            ("lib", ".dylib")
+        } else {
+            ("lib", ".so")
+        }
+    }
+
+    /// Prefix and suffix of static libraries, like `lib` and `.a` on Linux
+    pub fn staticlib_prefix_suffix(&self) -> (&'static str, &'static str) {
+        if self.is_msvc() {
+            ("", ".lib")
+        } else {
+            ("lib", ".a")
+        }
+    }
+}
+
+#[test]
+fn test_triple() {
+    assert_eq!(
+        Triple::new("aarch64-linux-android"),
+        Triple {
+            arch: "aarch64",
+            vendor: None,
+            os: "linux",
+            env: Some("android"),
+        }
+    );
+    assert_eq!(
+        Triple::new("x86_64-apple-darwin"),
+        Triple {
+            arch: "x86_64",
+            vendor: Some("apple"),
+            os: "darwin",
+            env: None,
+        }
+    );
+    assert_eq!(
+        Triple::new("wasm32-wasip1"),
+        Triple {
+            arch: "wasm32",
+            vendor: None,
+            os: "wasip1",
+            env: None,
+        }
+    );
+
+    let msvc = Triple::new("x86_64-pc-windows-msvc");
+    assert!(msvc.is_windows() && msvc.is_msvc());
+    assert!(!Triple::new("x86_64-pc-windows-gnu").is_msvc());
+    assert!(Triple::new("aarch64-apple-ios-sim").is_apple());
+    assert!(Triple::new("wasm32-unknown-emscripten").is_emscripten());
+}
@@ -1,21 +1,34 @@
-use crate::config::Config;
 use crate::error::{Error, Result};
 use crate::manifest::Manifest;
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 
-pub fn list_rust_files(dir: &Path) -> Result<Vec<PathBuf>> {
-    let mut files = vec![];
+/// Lists the targets that cargo [auto-discovers] in `dir`: every `<name>.rs` file, and every
+/// subdirectory containing a `<name>/main.rs` file, as `(name, path)` pairs sorted by path.
+///
+/// [auto-discovers]: https://doc.rust-lang.org/cargo/reference/cargo-targets.html#target-auto-discovery
+pub fn list_rust_targets(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut targets = vec![];
     if dir.exists() && dir.is_dir() {
         let entries = std::fs::read_dir(dir).map_err(|e| Error::Io(dir.to_owned(), e))?;
         for entry in entries {
             let path = entry.map_err(|e| Error::Io(dir.to_owned(), e))?.path();
-            if path.is_file() && path.extension() == Some(OsStr::new("rs")) {
-                files.push(path);
-            }
+            let (name, path) = if path.is_file() && path.extension() == Some(OsStr::new("rs")) {
+                (path.file_stem(), path.clone())
+            } else if path.join("main.rs").is_file() {
+                (path.file_name(), path.join("main.rs"))
+            } else {
+                continue;
+            };
+            let name = name
+                .and_then(OsStr::to_str)
+                .ok_or_else(|| Error::NonUtf8Path(path.clone()))?;
+            targets.push((name.to_owned(), path));
         }
     }
-    Ok(files)
+    targets.sort_by(|(_, a), (_, b)| a.cmp(b));
+    Ok(targets)
 }
 
 pub fn canonicalize(mut path: &Path) -> Result<PathBuf> {
@@ -25,18 +38,26 @@ pub fn canonicalize(mut path: &Path) -> Result<PathBuf> {
     dunce::canonicalize(path).map_err(|e| Error::Io(path.to_owned(), e))
 }
 
-/// Tries to find a package by the given `name` in the [workspace root] or member
-/// of the given [workspace] [`Manifest`], and possibly falls back to a potential
-/// manifest based on the working directory or `--manifest-path` as found by
-/// [`find_manifest()`] and passed as argument to `potential_manifest`.
+/// Tries to find the packages by the given `package_names` in the [workspace root]
+/// or members of the given [workspace] [`Manifest`], and possibly falls back to a
+/// potential manifest based on the working directory or `--manifest-path` as found
+/// by [`find_manifest()`] and passed as argument to `potential_manifest` when no
+/// names are given.
+///
+/// When `all_packages` is set (`--workspace`), `package_names` is ignored and the
+/// root package (if any) is returned together with every workspace member.
+/// Without `package_names` in the workspace root, [`default-members`] are selected,
+/// or all members if the root is a virtual manifest.
 ///
 /// [workspace root]: https://doc.rust-lang.org/cargo/reference/workspaces.html#root-package
 /// [workspace]: https://doc.rust-lang.org/cargo/reference/workspaces.html#workspaces
+/// [`default-members`]: https://doc.rust-lang.org/cargo/reference/workspaces.html#the-default-members-field
 pub fn find_package_manifest_in_workspace(
     (workspace_manifest_path, workspace_manifest): &(PathBuf, Manifest),
     (potential_manifest_path, potential_manifest): (PathBuf, Manifest),
-    package_name: Option<&str>,
-) -> Result<(PathBuf, Manifest)> {
+    package_names: &[String],
+    all_packages: bool,
+) -> Result<Vec<(PathBuf, Manifest)>> {
     let potential_manifest_dir = potential_manifest_path.parent().unwrap();
     let workspace_manifest_dir = workspace_manifest_path.parent().unwrap();
 
@@ -51,41 +72,133 @@ pub fn find_package_manifest_in_workspace(
         });
     }
 
-    match package_name {
-        // Any package in the workspace can be used if `-p` is used
-        Some(name) => {
-            // Check if the workspace manifest also contains a [package]
-            if let Some(package) = &workspace_manifest.package {
-                if package.name == name {
-                    return Ok((
-                        workspace_manifest_path.to_owned(),
-                        workspace_manifest.clone(),
-                    ));
-                }
-            }
+    if all_packages {
+        return Ok(workspace_manifest
+            .package
+            .is_some()
+            .then(|| (workspace_manifest_path.clone(), workspace_manifest.clone()))
+            .into_iter()
+            .chain(sorted_members(workspace_members))
+            .collect());
+    }
 
-            // Check all member packages inside the workspace
-            for (_manifest_dir, (manifest_path, manifest)) in workspace_members {
-                // .members() already checked for it having a package
-                let package = manifest.package.as_ref().unwrap();
-                if package.name == name {
-                    return Ok((manifest_path, manifest));
-                }
+    if package_names.is_empty() {
+        // Without package selection in the workspace root, fall back to `default-members`,
+        // or to all members if the workspace root is a virtual manifest
+        if workspace_manifest_path == &potential_manifest_path {
+            if let Some(default_members) =
+                workspace_manifest.default_members(workspace_manifest_dir)?
+            {
+                return default_members
+                    .into_iter()
+                    .map(|manifest_dir| {
+                        if manifest_dir == workspace_manifest_dir
+                            && workspace_manifest.package.is_some()
+                        {
+                            Ok((workspace_manifest_path.clone(), workspace_manifest.clone()))
+                        } else {
+                            workspace_members.get(&manifest_dir).cloned().ok_or(
+                                Error::DefaultMemberNotInWorkspace {
+                                    member: manifest_dir,
+                                    workspace_manifest: workspace_manifest_path.clone(),
+                                },
+                            )
+                        }
+                    })
+                    .collect();
+            } else if workspace_manifest.package.is_none() {
+                return Ok(sorted_members(workspace_members));
             }
-
-            Err(Error::PackageNotFound(
-                workspace_manifest_path.clone(),
-                name.to_owned(),
-            ))
         }
+
         // Otherwise use the manifest we just found, as long as it contains `[package]`
-        None => {
-            if potential_manifest.package.is_none() {
-                return Err(Error::NoPackageInManifest(potential_manifest_path));
-            }
-            Ok((potential_manifest_path, potential_manifest))
+        if potential_manifest.package.is_none() {
+            return Err(Error::NoPackageInManifest(potential_manifest_path));
+        }
+        return Ok(vec![(potential_manifest_path, potential_manifest)]);
+    }
+
+    // Any package in the workspace can be used if `-p` is used
+    let mut packages = Vec::<(PathBuf, Manifest)>::new();
+    for name in package_names {
+        // Skip packages that were passed multiple times
+        if packages
+            .iter()
+            .any(|(_, manifest)| &manifest.package.as_ref().unwrap().name == name)
+        {
+            continue;
         }
+
+        // Check if the workspace manifest also contains a [package]
+        let root_package = workspace_manifest
+            .package
+            .as_ref()
+            .filter(|package| &package.name == name)
+            .map(|_| (workspace_manifest_path.clone(), workspace_manifest.clone()));
+
+        // Check all member packages inside the workspace
+        let package = root_package.or_else(|| {
+            workspace_members
+                .values()
+                // .members() already checked for it having a package
+                .find(|(_, manifest)| &manifest.package.as_ref().unwrap().name == name)
+                .cloned()
+        });
+
+        packages.push(package.ok_or_else(|| {
+            Error::PackageNotFound(workspace_manifest_path.clone(), name.clone())
+        })?);
     }
+
+    Ok(packages)
+}
+
+/// Returns the manifests from [`Manifest::members()`] in a stable order
+fn sorted_members(members: HashMap<PathBuf, (PathBuf, Manifest)>) -> Vec<(PathBuf, Manifest)> {
+    let mut members = members.into_iter().collect::<Vec<_>>();
+    members.sort_by(|(a, _), (b, _)| a.cmp(b));
+    members
+        .into_iter()
+        .map(|(_manifest_dir, member)| member)
+        .collect()
+}
+
+/// Removes all packages whose name matches one of the glob `patterns` passed via
+/// `--exclude`, mirroring cargo's [package selection].  Every pattern must match at
+/// least one of the `packages` selected from the workspace at `workspace_manifest_path`.
+///
+/// [package selection]: https://doc.rust-lang.org/cargo/commands/cargo-build.html#package-selection
+pub fn exclude_packages(
+    workspace_manifest_path: &Path,
+    mut packages: Vec<(PathBuf, Manifest)>,
+    patterns: &[String],
+) -> Result<Vec<(PathBuf, Manifest)>> {
+    let patterns = patterns
+        .iter()
+        .map(|pattern| Ok((pattern, glob::Pattern::new(pattern)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    // All selected packages were already checked to contain a [package]
+    let package_name = |manifest: &Manifest| manifest.package.as_ref().unwrap().name.clone();
+
+    for (pattern, glob) in &patterns {
+        if !packages
+            .iter()
+            .any(|(_, manifest)| glob.matches(&package_name(manifest)))
+        {
+            return Err(Error::ExcludedPackageNotFound(
+                workspace_manifest_path.to_owned(),
+                pattern.to_string(),
+            ));
+        }
+    }
+
+    packages.retain(|(_, manifest)| {
+        let name = package_name(manifest);
+        !patterns.iter().any(|(_, glob)| glob.matches(&name))
+    });
+
+    Ok(packages)
 }
 
 /// Recursively walk up the directories until finding a `Cargo.toml`
@@ -102,33 +215,340 @@ pub fn find_manifest(path: &Path) -> Result<(PathBuf, Manifest)> {
     Ok((manifest_path, manifest))
 }
 
-/// Recursively walk up the directories until finding a `Cargo.toml`
-/// that contains a `[workspace]`
-pub fn find_workspace(potential_root: &Path) -> Result<Option<(PathBuf, Manifest)>> {
-    let potential_root = canonicalize(potential_root)?;
-    for manifest_path in potential_root
+/// Recursively walk up the directories from `potential_manifest_path` until finding a
+/// `Cargo.toml` that contains a `[workspace]`, skipping workspaces that list the package
+/// in their `workspace.exclude` array.  Excluded packages outside of any other workspace
+/// are their own workspace root, and hence [`None`] is returned.
+///
+/// If the package explicitly points to its workspace root via [`package.workspace`],
+/// that manifest is returned instead, as long as it contains a `[workspace]`.
+///
+/// [`package.workspace`]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-workspace-field
+pub fn find_workspace(
+    (potential_manifest_path, potential_manifest): &(PathBuf, Manifest),
+) -> Result<Option<(PathBuf, Manifest)>> {
+    let potential_manifest_dir = potential_manifest_path.parent().unwrap();
+
+    if let Some(workspace) = potential_manifest
+        .package
+        .as_ref()
+        .and_then(|package| package.workspace.as_ref())
+    {
+        let manifest_path =
+            canonicalize(&potential_manifest_dir.join(workspace))?.join("Cargo.toml");
+        let manifest = Manifest::parse_from_toml(&manifest_path)?;
+        if manifest.workspace.is_none() {
+            return Err(Error::PackageWorkspaceNotAWorkspace {
+                manifest: potential_manifest_path.clone(),
+                workspace_manifest: manifest_path,
+            });
+        }
+        return Ok(Some((manifest_path, manifest)));
+    }
+
+    for manifest_path in potential_manifest_dir
         .ancestors()
         .map(|dir| dir.join("Cargo.toml"))
         .filter(|manifest| manifest.exists())
     {
         let manifest = Manifest::parse_from_toml(&manifest_path)?;
-        if manifest.workspace.is_some() {
-            return Ok(Some((manifest_path, manifest)));
+        if let Some(workspace) = &manifest.workspace {
+            let workspace_root = manifest_path.parent().unwrap();
+            if !workspace.is_excluded(workspace_root, potential_manifest_dir) {
+                return Ok(Some((manifest_path, manifest)));
+            }
         }
     }
     Ok(None)
 }
 
-/// Returns the [`target-dir`] configured in `.cargo/config.toml` or `"target"` if not set.
+/// The name of `target` as used in the target directory: the file stem for paths to
+/// [custom target] `.json` files, or the triple itself otherwise.
 ///
-/// [`target-dir`]: https://doc.rust-lang.org/cargo/reference/config.html#buildtarget-dir
-pub fn get_target_dir_name(config: Option<&Config>) -> Result<String> {
-    if let Some(config) = config {
-        if let Some(build) = config.build.as_ref() {
-            if let Some(target_dir) = &build.target_dir {
-                return Ok(target_dir.clone());
+/// [custom target]: https://doc.rust-lang.org/rustc/targets/custom.html
+pub fn target_short_name(target: &str) -> &str {
+    if target.ends_with(".json") {
+        Path::new(target)
+            .file_stem()
+            .and_then(OsStr::to_str)
+            .unwrap_or(target)
+    } else {
+        target
+    }
+}
+
+/// Recursively merges the tables in `overlay` into `base`, where values from `overlay`
+/// take precedence over values from `base`, and returns the result.
+pub fn merge_toml(base: toml::Value, overlay: toml::Value) -> toml::Value {
+    match (base, overlay) {
+        (toml::Value::Table(mut base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                let value = match base.remove(&key) {
+                    Some(base) => merge_toml(base, value),
+                    None => value,
+                };
+                base.insert(key, value);
             }
+            toml::Value::Table(base)
+        }
+        (_, overlay) => overlay,
+    }
+}
+
+#[test]
+fn test_list_rust_targets() {
+    let dir = std::env::temp_dir().join("cargo-subcommand-test-list-rust-targets");
+    for subdir in ["server", "assets"] {
+        std::fs::create_dir_all(dir.join(subdir)).unwrap();
+    }
+    for file in [
+        "cli.rs",
+        "server/main.rs",
+        "server/util.rs",
+        "assets/icon.png",
+        "notes.txt",
+    ] {
+        std::fs::write(dir.join(file), "").unwrap();
+    }
+
+    assert_eq!(
+        list_rust_targets(&dir).unwrap(),
+        [
+            ("cli".to_owned(), dir.join("cli.rs")),
+            ("server".to_owned(), dir.join("server/main.rs")),
+        ]
+    );
+    assert!(list_rust_targets(&dir.join("missing")).unwrap().is_empty());
+}
+
+#[test]
+fn test_merge_toml() {
+    let workspace = toml::toml! {
+        assets = "assets"
+        features = ["a"]
+        [sdk]
+        min_sdk_version = 21
+        target_sdk_version = 30
+    };
+    let package = toml::toml! {
+        features = ["b"]
+        [sdk]
+        target_sdk_version = 33
+    };
+
+    assert_eq!(
+        merge_toml(workspace, package),
+        toml::toml! {
+            assets = "assets"
+            features = ["b"]
+            [sdk]
+            min_sdk_version = 21
+            target_sdk_version = 33
         }
+    );
+}
+
+/// Writes `files` into a fresh `cargo-subcommand-test-<name>` directory inside the
+/// temporary directory, and returns its canonicalized path
+#[cfg(test)]
+pub(crate) fn create_test_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cargo-subcommand-test-{name}"));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (path, contents) in files {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
     }
-    Ok("target".to_string())
+    canonicalize(&dir).unwrap()
+}
+
+/// The names of the selected `packages`, in order
+#[cfg(test)]
+pub(crate) fn package_names(packages: &[(PathBuf, Manifest)]) -> Vec<&str> {
+    packages
+        .iter()
+        .map(|(_, manifest)| manifest.package.as_ref().unwrap().name.as_str())
+        .collect()
+}
+
+#[test]
+fn test_select_packages() {
+    let dir = create_test_dir(
+        "select-packages",
+        &[
+            ("Cargo.toml", "[workspace]\nmembers = [\"a\", \"b\"]"),
+            ("a/Cargo.toml", "[package]\nname = \"a\""),
+            ("b/Cargo.toml", "[package]\nname = \"b\""),
+        ],
+    );
+    let workspace = find_manifest(&dir).unwrap();
+    let select = |names: &[&str]| {
+        let names = names
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+        find_package_manifest_in_workspace(&workspace, workspace.clone(), &names, false)
+    };
+
+    // Packages that are passed multiple times are only selected once
+    let packages = select(&["a", "b", "a"]).unwrap();
+    assert_eq!(package_names(&packages), ["a", "b"]);
+    assert_eq!(packages[1].0, dir.join("b/Cargo.toml"));
+
+    assert!(matches!(
+        select(&["a", "missing"]),
+        Err(Error::PackageNotFound(_, name)) if name == "missing"
+    ));
+}
+
+#[test]
+fn test_select_workspace() {
+    let dir = create_test_dir(
+        "select-workspace",
+        &[
+            (
+                "Cargo.toml",
+                "[package]\nname = \"root\"\n[workspace]\nmembers = [\"crates/*\"]",
+            ),
+            ("crates/y/Cargo.toml", "[package]\nname = \"y\""),
+            ("crates/x/Cargo.toml", "[package]\nname = \"x\""),
+        ],
+    );
+    let workspace = find_manifest(&dir).unwrap();
+
+    // The root package comes first, followed by all members sorted by path, regardless of
+    // the manifest in the working directory and of `-p`
+    for potential_manifest in [&dir, &dir.join("crates/y")] {
+        let potential_manifest = find_manifest(potential_manifest).unwrap();
+        let packages = find_package_manifest_in_workspace(
+            &workspace,
+            potential_manifest,
+            &["x".to_owned()],
+            true,
+        )
+        .unwrap();
+        assert_eq!(package_names(&packages), ["root", "x", "y"]);
+    }
+}
+
+#[test]
+fn test_exclude_packages() {
+    let dir = create_test_dir(
+        "exclude-packages",
+        &[
+            ("Cargo.toml", "[workspace]\nmembers = [\"*\"]"),
+            ("app/Cargo.toml", "[package]\nname = \"app\""),
+            ("tools-a/Cargo.toml", "[package]\nname = \"tools-a\""),
+            ("tools-b/Cargo.toml", "[package]\nname = \"tools-b\""),
+        ],
+    );
+    let workspace = find_manifest(&dir).unwrap();
+    let exclude = |patterns: &[&str]| {
+        let packages =
+            find_package_manifest_in_workspace(&workspace, workspace.clone(), &[], true).unwrap();
+        let patterns = patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        exclude_packages(&workspace.0, packages, &patterns)
+    };
+
+    assert_eq!(package_names(&exclude(&["tools-*"]).unwrap()), ["app"]);
+    assert_eq!(
+        package_names(&exclude(&["app", "tools-?"]).unwrap()),
+        Vec::<&str>::new()
+    );
+    // Every pattern has to match a selected package
+    assert!(matches!(
+        exclude(&["tools-*", "missing-*"]),
+        Err(Error::ExcludedPackageNotFound(_, pattern)) if pattern == "missing-*"
+    ));
+
+    #[cfg(feature = "clap")]
+    {
+        use clap::Parser;
+        let args = crate::Args::parse_from(["cargo-subcommand", "--exclude", "tools-*"]);
+        assert!(matches!(
+            crate::Subcommand::new(args),
+            Err(Error::ExcludeWithoutWorkspace)
+        ));
+    }
+}
+
+#[test]
+fn test_default_members() {
+    let dir = create_test_dir(
+        "default-members",
+        &[
+            (
+                "virtual/Cargo.toml",
+                "[workspace]\nmembers = [\"crates/*\"]\ndefault-members = [\"crates/b*\"]",
+            ),
+            ("virtual/crates/a/Cargo.toml", "[package]\nname = \"a\""),
+            ("virtual/crates/b1/Cargo.toml", "[package]\nname = \"b1\""),
+            ("virtual/crates/b2/Cargo.toml", "[package]\nname = \"b2\""),
+            (
+                "root/Cargo.toml",
+                "[package]\nname = \"root\"\n[workspace]\nmembers = [\"a\"]\ndefault-members = [\".\"]",
+            ),
+            ("root/a/Cargo.toml", "[package]\nname = \"a\""),
+            (
+                "invalid/Cargo.toml",
+                "[workspace]\nmembers = [\"a\"]\ndefault-members = [\"other\"]",
+            ),
+            ("invalid/a/Cargo.toml", "[package]\nname = \"a\""),
+            ("invalid/other/Cargo.toml", "[package]\nname = \"other\""),
+        ],
+    );
+    let select = |root: &str| {
+        let workspace = find_manifest(&dir.join(root)).unwrap();
+        find_package_manifest_in_workspace(&workspace, workspace.clone(), &[], false)
+    };
+
+    // Default members are glob patterns, and may include the root package
+    assert_eq!(package_names(&select("virtual").unwrap()), ["b1", "b2"]);
+    assert_eq!(package_names(&select("root").unwrap()), ["root"]);
+
+    assert!(matches!(
+        select("invalid"),
+        Err(Error::DefaultMemberNotInWorkspace { member, .. }) if member == dir.join("invalid/other")
+    ));
+}
+
+#[test]
+fn test_package_workspace() {
+    let dir = create_test_dir(
+        "package-workspace",
+        &[
+            ("ws/Cargo.toml", "[workspace]\nmembers = [\"../oot\"]"),
+            (
+                "oot/Cargo.toml",
+                "[package]\nname = \"oot\"\nworkspace = \"../ws\"",
+            ),
+            (
+                "unlisted/Cargo.toml",
+                "[package]\nname = \"unlisted\"\nworkspace = \"../ws\"",
+            ),
+            (
+                "not-a-workspace/Cargo.toml",
+                "[package]\nname = \"not-a-workspace\"\nworkspace = \"../oot\"",
+            ),
+        ],
+    );
+    let select = |package: &str| {
+        let manifest = find_manifest(&dir.join(package)).unwrap();
+        let workspace = find_workspace(&manifest)?.unwrap();
+        assert_eq!(workspace.0, dir.join("ws/Cargo.toml"));
+        find_package_manifest_in_workspace(&workspace, manifest, &[], false)
+    };
+
+    // The root is found outside of the ancestors of the package, but has to list it
+    assert_eq!(package_names(&select("oot").unwrap()), ["oot"]);
+    assert!(matches!(
+        select("unlisted"),
+        Err(Error::ManifestNotInWorkspace { .. })
+    ));
+    assert!(matches!(
+        select("not-a-workspace"),
+        Err(Error::PackageWorkspaceNotAWorkspace { manifest, workspace_manifest })
+            if manifest == dir.join("not-a-workspace/Cargo.toml")
+                && workspace_manifest == dir.join("oot/Cargo.toml")
+    ));
 }